@@ -0,0 +1,157 @@
+use core::fmt;
+use core::iter::Fuse;
+
+use crate::ring::ArrayRing;
+
+/// An iterator with lookahead of up to `N` items, buffered without allocating.
+///
+/// This behaves like [`Lookahead`], except that the items are buffered inline and looking
+/// further ahead than the capacity allows is reported as a [`CapacityError`].
+///
+/// [`Lookahead`]: crate::Lookahead
+#[derive(Clone, Debug)]
+pub struct ArrayLookahead<I: Iterator, const N: usize> {
+    iter: Fuse<I>,
+    ring: ArrayRing<I::Item, N>,
+}
+
+impl<I: Iterator, const N: usize> ArrayLookahead<I, N> {
+    /// Create an [`ArrayLookahead`] iterator over the given iterable.
+    pub fn new<T>(iterable: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        ArrayLookahead {
+            iter: iterable.into_iter().fuse(),
+            ring: ArrayRing::new(),
+        }
+    }
+
+    /// Return the number of items that can be looked ahead, which is `N`.
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Return a reference to the item `n` iterations ahead without advancing the iterator.
+    ///
+    /// Returns an error if `n` is not less than `N`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::ArrayLookahead;
+    ///
+    /// let mut iter = ArrayLookahead::<_, 2>::new(1..=3);
+    ///
+    /// assert_eq!(iter.lookahead(1), Ok(Some(&2)));
+    /// assert!(iter.lookahead(2).is_err());
+    /// ```
+    pub fn lookahead(&mut self, n: usize) -> Result<Option<&I::Item>, CapacityError> {
+        if n >= N {
+            return Err(CapacityError { capacity: N });
+        }
+        while self.ring.len() <= n {
+            match self.iter.next() {
+                Some(item) => {
+                    let _ = self.ring.push_back(item);
+                }
+                None => break,
+            }
+        }
+        Ok(self.ring.get(n))
+    }
+
+    /// Return a mutable reference to the item `n` iterations ahead without advancing the
+    /// iterator.
+    ///
+    /// Returns an error if `n` is not less than `N`.
+    pub fn lookahead_mut(&mut self, n: usize) -> Result<Option<&mut I::Item>, CapacityError> {
+        self.lookahead(n)?;
+        Ok(self.ring.get_mut(n))
+    }
+}
+
+impl<I, const N: usize> Iterator for ArrayLookahead<I, N>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ring.pop_front().or_else(|| self.iter.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let queued = self.ring.len();
+        let (lower, upper) = self.iter.size_hint();
+        (lower + queued, upper.map(|n| n + queued))
+    }
+}
+
+impl<I, const N: usize> DoubleEndedIterator for ArrayLookahead<I, N>
+where
+    I: DoubleEndedIterator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().or_else(|| self.ring.pop_back())
+    }
+}
+
+impl<I, const N: usize> ExactSizeIterator for ArrayLookahead<I, N> where I: ExactSizeIterator {}
+
+/// The error returned when looking further ahead than an [`ArrayLookahead`] can buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapacityError {
+    capacity: usize,
+}
+
+impl CapacityError {
+    /// Return the capacity that was exceeded.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lookahead exceeds capacity of {} items", self.capacity)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CapacityError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookahead() {
+        let mut iter = ArrayLookahead::<_, 2>::new([1, 2, 3].iter());
+        assert_eq!(iter.lookahead(0), Ok(Some(&&1)));
+        assert_eq!(iter.lookahead(1), Ok(Some(&&2)));
+        assert_eq!(iter.lookahead(2), Err(CapacityError { capacity: 2 }));
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.lookahead(1), Ok(Some(&&3)));
+        assert_eq!(iter.lookahead(0), Ok(Some(&&2)));
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut iter = ArrayLookahead::<_, 0>::new([1].iter());
+        assert!(iter.lookahead(0).is_err());
+        assert_eq!(iter.next(), Some(&1));
+    }
+
+    #[test]
+    fn next_back() {
+        let mut iter = ArrayLookahead::<_, 2>::new([1, 2].iter());
+        let _ = iter.lookahead(1);
+        assert_eq!(iter.next_back(), Some(&2));
+        assert_eq!(iter.lookahead(0), Ok(Some(&&1)));
+        assert_eq!(iter.len(), 1);
+    }
+}
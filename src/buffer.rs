@@ -0,0 +1,511 @@
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::ring::{ArrayRing, Ring};
+
+/// Storage for the items buffered by a [`Lookahead`].
+///
+/// Indices are relative to the front of the buffer, which holds the next item. A buffer may
+/// have a fixed capacity, in which case it reports [`is_full`] once it cannot take more items
+/// and hands back any item it cannot store.
+///
+/// [`Lookahead`]: crate::Lookahead
+/// [`is_full`]: LookaheadBuffer::is_full
+pub trait LookaheadBuffer<T> {
+    /// Return the number of buffered items.
+    fn len(&self) -> usize;
+
+    /// Return `true` if no items are buffered.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return `true` if no more items can be buffered.
+    fn is_full(&self) -> bool {
+        false
+    }
+
+    /// Return the number of items that can be buffered without reallocating, if known.
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Return a reference to the item at `index`.
+    fn get(&self, index: usize) -> Option<&T>;
+
+    /// Return a mutable reference to the item at `index`.
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>;
+
+    /// Append `item` to the back of the buffer, or return it if the buffer is full.
+    fn push_back(&mut self, item: T) -> Result<(), T>;
+
+    /// Prepend `item` to the front of the buffer, or return it if the buffer is full.
+    fn push_front(&mut self, item: T) -> Result<(), T>;
+
+    /// Remove and return the item at the front of the buffer.
+    fn pop_front(&mut self) -> Option<T>;
+
+    /// Remove and return the item at the back of the buffer.
+    fn pop_back(&mut self) -> Option<T>;
+
+    /// Remove and drop the first `n` items, or every item if fewer than `n` are buffered.
+    fn drop_front(&mut self, n: usize) {
+        for _ in 0..n {
+            if self.pop_front().is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Insert `item` at `index`, or return it if the buffer is full.
+    ///
+    /// Implementations may panic if `index` is greater than the length.
+    fn insert(&mut self, index: usize, item: T) -> Result<(), T>;
+
+    /// Remove and return the item at `index`.
+    fn remove(&mut self, index: usize) -> Option<T>;
+
+    /// Rearrange the buffer so that its items are stored in order, and return them as a slice.
+    fn make_contiguous(&mut self) -> &mut [T];
+}
+
+impl<T, B> LookaheadBuffer<T> for &mut B
+where
+    B: LookaheadBuffer<T> + ?Sized,
+{
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn is_full(&self) -> bool {
+        (**self).is_full()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        (**self).capacity()
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        (**self).get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        (**self).get_mut(index)
+    }
+
+    fn push_back(&mut self, item: T) -> Result<(), T> {
+        (**self).push_back(item)
+    }
+
+    fn push_front(&mut self, item: T) -> Result<(), T> {
+        (**self).push_front(item)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        (**self).pop_front()
+    }
+
+    fn pop_back(&mut self) -> Option<T> {
+        (**self).pop_back()
+    }
+
+    fn drop_front(&mut self, n: usize) {
+        (**self).drop_front(n)
+    }
+
+    fn insert(&mut self, index: usize, item: T) -> Result<(), T> {
+        (**self).insert(index, item)
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        (**self).remove(index)
+    }
+
+    fn make_contiguous(&mut self) -> &mut [T] {
+        (**self).make_contiguous()
+    }
+}
+
+impl<T> LookaheadBuffer<T> for VecDeque<T> {
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(VecDeque::capacity(self))
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        VecDeque::get(self, index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        VecDeque::get_mut(self, index)
+    }
+
+    fn push_back(&mut self, item: T) -> Result<(), T> {
+        VecDeque::push_back(self, item);
+        Ok(())
+    }
+
+    fn push_front(&mut self, item: T) -> Result<(), T> {
+        VecDeque::push_front(self, item);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        VecDeque::pop_front(self)
+    }
+
+    fn pop_back(&mut self) -> Option<T> {
+        VecDeque::pop_back(self)
+    }
+
+    fn drop_front(&mut self, n: usize) {
+        let n = n.min(VecDeque::len(self));
+        self.drain(..n);
+    }
+
+    fn insert(&mut self, index: usize, item: T) -> Result<(), T> {
+        VecDeque::insert(self, index, item);
+        Ok(())
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        VecDeque::remove(self, index)
+    }
+
+    fn make_contiguous(&mut self) -> &mut [T] {
+        VecDeque::make_contiguous(self)
+    }
+}
+
+/// A buffer that stores up to `N` items inline and moves them to the heap once it outgrows
+/// them.
+///
+/// This suits iterators that rarely look more than a few items ahead, since they never
+/// allocate. A [`Lookahead`] uses a `SmallBuffer` with room for two items unless it is given
+/// another buffer.
+///
+/// [`Lookahead`]: crate::Lookahead
+pub struct SmallBuffer<T, const N: usize> {
+    storage: Storage<T, N>,
+}
+
+enum Storage<T, const N: usize> {
+    Inline(ArrayRing<T, N>),
+    Spilled(VecDeque<T>),
+}
+
+impl<T, const N: usize> SmallBuffer<T, N> {
+    /// Create an empty [`SmallBuffer`].
+    pub fn new() -> Self {
+        SmallBuffer {
+            storage: Storage::Inline(ArrayRing::new()),
+        }
+    }
+
+    /// Create an empty [`SmallBuffer`] that can hold `capacity` items without reallocating.
+    ///
+    /// If `capacity` is greater than `N`, the items are stored on the heap from the start.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let storage = if capacity > N {
+            Storage::Spilled(VecDeque::with_capacity(capacity))
+        } else {
+            Storage::Inline(ArrayRing::new())
+        };
+        SmallBuffer { storage }
+    }
+
+    /// Return `true` if the items have been moved to the heap.
+    pub fn spilled(&self) -> bool {
+        matches!(self.storage, Storage::Spilled(_))
+    }
+
+    /// Move the items to the heap, if they are not there already.
+    fn reserve(&mut self) -> &mut VecDeque<T> {
+        if let Storage::Inline(ring) = &mut self.storage {
+            let mut items = VecDeque::with_capacity(2 * N.max(1));
+            while let Some(item) = ring.pop_front() {
+                items.push_back(item);
+            }
+            self.storage = Storage::Spilled(items);
+        }
+        match &mut self.storage {
+            Storage::Spilled(items) => items,
+            Storage::Inline(_) => unreachable!(),
+        }
+    }
+}
+
+impl<T, const N: usize> LookaheadBuffer<T> for SmallBuffer<T, N> {
+    fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Inline(ring) => ring.len(),
+            Storage::Spilled(items) => items.len(),
+        }
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        match &self.storage {
+            Storage::Inline(_) => Some(N),
+            Storage::Spilled(items) => Some(items.capacity()),
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        match &self.storage {
+            Storage::Inline(ring) => ring.get(index),
+            Storage::Spilled(items) => items.get(index),
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        match &mut self.storage {
+            Storage::Inline(ring) => ring.get_mut(index),
+            Storage::Spilled(items) => items.get_mut(index),
+        }
+    }
+
+    fn push_back(&mut self, item: T) -> Result<(), T> {
+        if let Storage::Inline(ring) = &mut self.storage {
+            match ring.push_back(item) {
+                Ok(()) => return Ok(()),
+                Err(item) => self.reserve().push_back(item),
+            }
+        } else {
+            self.reserve().push_back(item);
+        }
+        Ok(())
+    }
+
+    fn push_front(&mut self, item: T) -> Result<(), T> {
+        if let Storage::Inline(ring) = &mut self.storage {
+            match ring.push_front(item) {
+                Ok(()) => return Ok(()),
+                Err(item) => self.reserve().push_front(item),
+            }
+        } else {
+            self.reserve().push_front(item);
+        }
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        match &mut self.storage {
+            Storage::Inline(ring) => ring.pop_front(),
+            Storage::Spilled(items) => items.pop_front(),
+        }
+    }
+
+    fn pop_back(&mut self) -> Option<T> {
+        match &mut self.storage {
+            Storage::Inline(ring) => ring.pop_back(),
+            Storage::Spilled(items) => items.pop_back(),
+        }
+    }
+
+    fn drop_front(&mut self, n: usize) {
+        match &mut self.storage {
+            Storage::Inline(ring) => {
+                for _ in 0..n {
+                    if ring.pop_front().is_none() {
+                        break;
+                    }
+                }
+            }
+            Storage::Spilled(items) => items.drop_front(n),
+        }
+    }
+
+    fn insert(&mut self, index: usize, item: T) -> Result<(), T> {
+        if let Storage::Inline(ring) = &mut self.storage {
+            match ring.insert(index, item) {
+                Ok(()) => return Ok(()),
+                Err(item) => self.reserve().insert(index, item),
+            }
+        } else {
+            self.reserve().insert(index, item);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        match &mut self.storage {
+            Storage::Inline(ring) => ring.remove(index),
+            Storage::Spilled(items) => items.remove(index),
+        }
+    }
+
+    fn make_contiguous(&mut self) -> &mut [T] {
+        match &mut self.storage {
+            Storage::Inline(ring) => ring.make_contiguous(),
+            Storage::Spilled(items) => items.make_contiguous(),
+        }
+    }
+}
+
+impl<T, const N: usize> Default for SmallBuffer<T, N> {
+    fn default() -> Self {
+        SmallBuffer::new()
+    }
+}
+
+impl<T, const N: usize> Clone for SmallBuffer<T, N>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        let storage = match &self.storage {
+            Storage::Inline(ring) => Storage::Inline(ring.clone()),
+            Storage::Spilled(items) => Storage::Spilled(items.clone()),
+        };
+        SmallBuffer { storage }
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for SmallBuffer<T, N>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.storage {
+            Storage::Inline(ring) => ring.fmt(f),
+            Storage::Spilled(items) => items.fmt(f),
+        }
+    }
+}
+
+/// A fixed-capacity buffer over caller-provided storage.
+///
+/// The buffer holds at most as many items as there are slots, and never allocates. Once it is
+/// full, a [`Lookahead`] cannot look any further ahead.
+///
+/// [`Lookahead`]: crate::Lookahead
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::{Lookahead, RingBuffer};
+/// use std::mem::MaybeUninit;
+///
+/// let mut slots = [MaybeUninit::uninit(); 2];
+/// let mut iter = Lookahead::with_buffer(1..=3, RingBuffer::new(&mut slots));
+///
+/// assert_eq!(iter.lookahead(1), Some(&2));
+/// assert_eq!(iter.lookahead(2), None);
+/// assert_eq!(iter.next(), Some(1));
+/// assert_eq!(iter.lookahead(1), Some(&3));
+/// ```
+pub struct RingBuffer<'a, T> {
+    ring: Ring<T, &'a mut [MaybeUninit<T>]>,
+}
+
+impl<'a, T> RingBuffer<'a, T> {
+    /// Create an empty [`RingBuffer`] that stores its items in `slots`.
+    ///
+    /// Any values already in `slots` are ignored and will not be dropped.
+    pub fn new(slots: &'a mut [MaybeUninit<T>]) -> Self {
+        RingBuffer {
+            ring: Ring::from_slots(slots),
+        }
+    }
+
+    /// Return the number of items the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+}
+
+impl<'a, T> LookaheadBuffer<T> for RingBuffer<'a, T> {
+    fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    fn is_full(&self) -> bool {
+        self.ring.is_full()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.ring.capacity())
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        self.ring.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.ring.get_mut(index)
+    }
+
+    fn push_back(&mut self, item: T) -> Result<(), T> {
+        self.ring.push_back(item)
+    }
+
+    fn push_front(&mut self, item: T) -> Result<(), T> {
+        self.ring.push_front(item)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.ring.pop_front()
+    }
+
+    fn pop_back(&mut self) -> Option<T> {
+        self.ring.pop_back()
+    }
+
+    fn insert(&mut self, index: usize, item: T) -> Result<(), T> {
+        self.ring.insert(index, item)
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        self.ring.remove(index)
+    }
+
+    fn make_contiguous(&mut self) -> &mut [T] {
+        self.ring.make_contiguous()
+    }
+}
+
+impl<'a, T> fmt::Debug for RingBuffer<'a, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.ring.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_buffer_spills() {
+        let mut buffer = SmallBuffer::<_, 2>::new();
+        assert_eq!(buffer.push_back(1), Ok(()));
+        assert_eq!(buffer.push_front(0), Ok(()));
+        assert!(!buffer.spilled());
+        assert_eq!(buffer.insert(1, 5), Ok(()));
+        assert!(buffer.spilled());
+        assert_eq!(buffer.make_contiguous(), &mut [0, 5, 1]);
+        assert_eq!(buffer.remove(1), Some(5));
+        assert_eq!(buffer.pop_back(), Some(1));
+        assert_eq!(buffer.pop_front(), Some(0));
+        assert!(buffer.is_empty());
+        assert!(SmallBuffer::<u8, 2>::with_capacity(3).spilled());
+    }
+
+    #[test]
+    fn ring_buffer_is_bounded() {
+        let mut slots = [MaybeUninit::uninit(); 1];
+        let mut buffer = RingBuffer::new(&mut slots);
+        assert_eq!(buffer.push_back(1), Ok(()));
+        assert!(buffer.is_full());
+        assert_eq!(buffer.push_front(0), Err(0));
+        assert_eq!(buffer.get(0), Some(&1));
+    }
+}
@@ -0,0 +1,468 @@
+use std::convert::TryInto;
+use std::io::{self, BufRead, Read};
+
+/// The number of bytes read from the underlying reader at a time, unless specified otherwise.
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// The maximum length of an encoded 64-bit varint.
+const MAX_VARINT_LEN: usize = 10;
+
+/// Attach a computed doc comment to an item.
+macro_rules! doc_comment {
+    ($doc:expr, $($item:tt)*) => {
+        #[doc = $doc]
+        $($item)*
+    };
+}
+
+/// Define the peeking and reading methods for fixed-size numbers.
+macro_rules! numbers {
+    ($($ty:ident $from:ident $order:literal $peek:ident $read:ident;)*) => {
+        $(
+            doc_comment! {
+                concat!(
+                    "Return the next `", stringify!($ty), "`, stored in ", $order,
+                    " byte order, without consuming it.\n\n",
+                    "Returns `None` if the input ends first.",
+                ),
+                pub fn $peek(&mut self) -> io::Result<Option<$ty>> {
+                    Ok(self.peek_array()?.map($ty::$from))
+                }
+            }
+
+            doc_comment! {
+                concat!(
+                    "Consume and return the next `", stringify!($ty), "`, stored in ", $order,
+                    " byte order.\n\n",
+                    "Returns `None`, consuming nothing, if the input ends first.",
+                ),
+                pub fn $read(&mut self) -> io::Result<Option<$ty>> {
+                    let value = self.$peek()?;
+                    if value.is_some() {
+                        self.consume(std::mem::size_of::<$ty>());
+                    }
+                    Ok(value)
+                }
+            }
+        )*
+    };
+}
+
+/// A reader with arbitrary lookahead.
+///
+/// Bytes are read from the underlying reader in chunks of at least the capacity and buffered
+/// until they are consumed, either through [`Read`] and [`BufRead`] or the peeking methods.
+/// This makes it possible to inspect a header and then hand the same reader to a decoder.
+#[derive(Debug)]
+pub struct ByteLookahead<R> {
+    reader: R,
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: Read> ByteLookahead<R> {
+    /// Create a [`ByteLookahead`] reader over the given reader.
+    pub fn new(reader: R) -> Self {
+        ByteLookahead::with_capacity(reader, DEFAULT_CAPACITY)
+    }
+
+    /// Create a [`ByteLookahead`] reader over the given reader that reads at least `capacity`
+    /// bytes at a time.
+    pub fn with_capacity(reader: R, capacity: usize) -> Self {
+        ByteLookahead {
+            reader,
+            buf: vec![0; capacity.max(1)],
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// Return the next `n` bytes without consuming them.
+    ///
+    /// Fewer than `n` bytes are returned only if the reader reaches the end of its input.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::ByteLookahead;
+    /// use std::io::Read;
+    ///
+    /// let mut reader = ByteLookahead::new(&b"GIF89a..."[..]);
+    ///
+    /// assert_eq!(reader.peek_bytes(3)?, b"GIF");
+    ///
+    /// let mut contents = String::new();
+    /// reader.read_to_string(&mut contents)?;
+    /// assert_eq!(contents, "GIF89a...");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn peek_bytes(&mut self, n: usize) -> io::Result<&[u8]> {
+        while self.filled - self.pos < n {
+            if self.fill(n)? == 0 {
+                break;
+            }
+        }
+        let end = self.filled.min(self.pos + n);
+        Ok(&self.buf[self.pos..end])
+    }
+
+    /// Return the next byte without consuming it.
+    ///
+    /// Returns `None` if the input has ended.
+    pub fn peek_u8(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.peek_bytes(1)?.first().copied())
+    }
+
+    /// Consume and return the next byte.
+    ///
+    /// Returns `None` if the input has ended.
+    pub fn read_u8(&mut self) -> io::Result<Option<u8>> {
+        let value = self.peek_u8()?;
+        if value.is_some() {
+            self.consume(1);
+        }
+        Ok(value)
+    }
+
+    /// Return the next byte as an `i8` without consuming it.
+    ///
+    /// Returns `None` if the input has ended.
+    pub fn peek_i8(&mut self) -> io::Result<Option<i8>> {
+        Ok(self.peek_u8()?.map(|byte| byte as i8))
+    }
+
+    /// Consume and return the next byte as an `i8`.
+    ///
+    /// Returns `None` if the input has ended.
+    pub fn read_i8(&mut self) -> io::Result<Option<i8>> {
+        Ok(self.read_u8()?.map(|byte| byte as i8))
+    }
+
+    numbers! {
+        u16 from_le_bytes "little-endian" peek_u16_le read_u16_le;
+        u16 from_be_bytes "big-endian" peek_u16_be read_u16_be;
+        u32 from_le_bytes "little-endian" peek_u32_le read_u32_le;
+        u32 from_be_bytes "big-endian" peek_u32_be read_u32_be;
+        u64 from_le_bytes "little-endian" peek_u64_le read_u64_le;
+        u64 from_be_bytes "big-endian" peek_u64_be read_u64_be;
+        i16 from_le_bytes "little-endian" peek_i16_le read_i16_le;
+        i16 from_be_bytes "big-endian" peek_i16_be read_i16_be;
+        i32 from_le_bytes "little-endian" peek_i32_le read_i32_le;
+        i32 from_be_bytes "big-endian" peek_i32_be read_i32_be;
+        i64 from_le_bytes "little-endian" peek_i64_le read_i64_le;
+        i64 from_be_bytes "big-endian" peek_i64_be read_i64_be;
+        f32 from_le_bytes "little-endian" peek_f32_le read_f32_le;
+        f32 from_be_bytes "big-endian" peek_f32_be read_f32_be;
+        f64 from_le_bytes "little-endian" peek_f64_le read_f64_le;
+        f64 from_be_bytes "big-endian" peek_f64_be read_f64_be;
+    }
+
+    /// Return the next unsigned LEB128 varint, as used by Protocol Buffers, without consuming
+    /// it.
+    ///
+    /// Returns `None` if the input ends before the varint does, and an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the varint does not fit in a `u64`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::ByteLookahead;
+    ///
+    /// let mut reader = ByteLookahead::new(&[0xac, 0x02, 0x01][..]);
+    ///
+    /// assert_eq!(reader.peek_varint()?, Some(300));
+    /// assert_eq!(reader.read_varint()?, Some(300));
+    /// assert_eq!(reader.read_varint()?, Some(1));
+    /// assert_eq!(reader.read_varint()?, None);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn peek_varint(&mut self) -> io::Result<Option<u64>> {
+        Ok(self.peek_leb128(false)?.map(|(value, _)| value))
+    }
+
+    /// Consume and return the next unsigned LEB128 varint.
+    ///
+    /// Returns `None`, consuming nothing, if the input ends before the varint does. See
+    /// [`ByteLookahead::peek_varint`].
+    pub fn read_varint(&mut self) -> io::Result<Option<u64>> {
+        Ok(self.read_leb128(false)?.map(|(value, _)| value))
+    }
+
+    /// Return the next signed LEB128 varint without consuming it.
+    ///
+    /// Returns `None` if the input ends before the varint does, and an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the varint does not fit in an `i64`.
+    pub fn peek_varint_signed(&mut self) -> io::Result<Option<i64>> {
+        Ok(self
+            .peek_leb128(true)?
+            .map(|(value, len)| sign_extend(value, len)))
+    }
+
+    /// Consume and return the next signed LEB128 varint.
+    ///
+    /// Returns `None`, consuming nothing, if the input ends before the varint does.
+    pub fn read_varint_signed(&mut self) -> io::Result<Option<i64>> {
+        Ok(self
+            .read_leb128(true)?
+            .map(|(value, len)| sign_extend(value, len)))
+    }
+
+    /// Return the next ZigZag-encoded varint, as used for the `sint` types of Protocol
+    /// Buffers, without consuming it.
+    ///
+    /// Returns `None` if the input ends before the varint does.
+    pub fn peek_varint_zigzag(&mut self) -> io::Result<Option<i64>> {
+        Ok(self.peek_varint()?.map(zigzag))
+    }
+
+    /// Consume and return the next ZigZag-encoded varint.
+    ///
+    /// Returns `None`, consuming nothing, if the input ends before the varint does.
+    pub fn read_varint_zigzag(&mut self) -> io::Result<Option<i64>> {
+        Ok(self.read_varint()?.map(zigzag))
+    }
+
+    /// Return the bytes that are currently buffered.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    ///
+    /// Reading from it directly skips the bytes that are currently buffered.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwrap this [`ByteLookahead`], returning the underlying reader.
+    ///
+    /// Any buffered bytes are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Return the next `N` bytes without consuming them, or `None` if the input ends first.
+    fn peek_array<const N: usize>(&mut self) -> io::Result<Option<[u8; N]>> {
+        let bytes = self.peek_bytes(N)?;
+        Ok(bytes.try_into().ok())
+    }
+
+    /// Return the raw bits and encoded length of the next LEB128 varint.
+    ///
+    /// A `signed` varint may end in the sign extension of its 64th bit.
+    fn peek_leb128(&mut self, signed: bool) -> io::Result<Option<(u64, usize)>> {
+        let bytes = self.peek_bytes(MAX_VARINT_LEN)?;
+        let mut value = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            let bits = u64::from(byte & 0x7f);
+            if i == MAX_VARINT_LEN - 1 {
+                let fits = if signed {
+                    byte == 0 || byte == 0x7f
+                } else {
+                    byte <= 1
+                };
+                if !fits {
+                    break;
+                }
+            }
+            value |= bits << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(Some((value, i + 1)));
+            }
+        }
+        if bytes.len() < MAX_VARINT_LEN {
+            Ok(None)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "varint does not fit in 64 bits",
+            ))
+        }
+    }
+
+    fn read_leb128(&mut self, signed: bool) -> io::Result<Option<(u64, usize)>> {
+        let varint = self.peek_leb128(signed)?;
+        if let Some((_, len)) = varint {
+            self.consume(len);
+        }
+        Ok(varint)
+    }
+
+    /// Read once from the underlying reader, making room for at least `n` buffered bytes.
+    ///
+    /// Returns the number of bytes read, which is `0` at the end of the input.
+    fn fill(&mut self, n: usize) -> io::Result<usize> {
+        if self.pos == self.filled {
+            self.pos = 0;
+            self.filled = 0;
+        }
+        if self.buf.len() - self.pos < n {
+            self.buf.copy_within(self.pos..self.filled, 0);
+            self.filled -= self.pos;
+            self.pos = 0;
+        }
+        if self.buf.len() < n {
+            self.buf.resize(n, 0);
+        }
+        loop {
+            match self.reader.read(&mut self.buf[self.filled..]) {
+                Ok(read) => {
+                    self.filled += read;
+                    return Ok(read);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Interpret the low `7 * len` bits of `value` as a two's complement number.
+fn sign_extend(value: u64, len: usize) -> i64 {
+    let unused = 64usize.saturating_sub(7 * len) as u32;
+    ((value << unused) as i64) >> unused
+}
+
+/// Decode a ZigZag-encoded number.
+fn zigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+impl<R: Read> Read for ByteLookahead<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        // Large reads bypass the buffer when it is empty.
+        if self.pos == self.filled && out.len() >= self.buf.len() {
+            return self.reader.read(out);
+        }
+        let read = self.fill_buf()?.read(out)?;
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<R: Read> BufRead for ByteLookahead<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            self.fill(1)?;
+        }
+        Ok(self.buffer())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = self.filled.min(self.pos + amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader that returns at most `chunk` bytes per read and counts the reads.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        reads: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            let n = out.len().min(self.chunk).min(self.data.len());
+            out[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn peek_bytes() {
+        let inner = Trickle {
+            data: b"hello, world",
+            chunk: 5,
+            reads: 0,
+        };
+        let mut reader = ByteLookahead::with_capacity(inner, 4);
+        assert_eq!(reader.peek_bytes(7).unwrap(), b"hello, ");
+        assert_eq!(reader.get_ref().reads, 2);
+        assert_eq!(reader.peek_bytes(2).unwrap(), b"he");
+        assert_eq!(reader.get_ref().reads, 2);
+        assert_eq!(reader.peek_bytes(100).unwrap(), b"hello, world");
+        assert_eq!(reader.peek_bytes(100).unwrap(), b"hello, world");
+    }
+
+    #[test]
+    fn read_after_peek() {
+        let mut reader = ByteLookahead::with_capacity(&b"abcdef"[..], 2);
+        assert_eq!(reader.peek_bytes(3).unwrap(), b"abc");
+        let mut out = [0; 2];
+        assert_eq!(reader.read(&mut out).unwrap(), 2);
+        assert_eq!(&out, b"ab");
+        assert_eq!(reader.peek_bytes(4).unwrap(), b"cdef");
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"cdef");
+        assert_eq!(reader.peek_bytes(1).unwrap(), b"");
+    }
+
+    #[test]
+    fn buf_read() {
+        let mut reader = ByteLookahead::with_capacity(&b"one\ntwo\n"[..], 3);
+        assert_eq!(reader.peek_bytes(1).unwrap(), b"o");
+        let lines = reader.lines().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn numbers() {
+        let mut reader = ByteLookahead::with_capacity(&[1, 2, 3, 4, 0xff, 0, 0, 0x80, 0x3f][..], 2);
+        assert_eq!(reader.peek_u16_le().unwrap(), Some(0x0201));
+        assert_eq!(reader.peek_u16_be().unwrap(), Some(0x0102));
+        assert_eq!(reader.read_u32_be().unwrap(), Some(0x0102_0304));
+        assert_eq!(reader.peek_i8().unwrap(), Some(-1));
+        assert_eq!(reader.read_i8().unwrap(), Some(-1));
+        assert_eq!(reader.peek_i16_le().unwrap(), Some(0));
+        assert_eq!(reader.peek_u64_le().unwrap(), None);
+        assert_eq!(reader.read_f32_le().unwrap(), Some(1.0));
+        assert_eq!(reader.read_u8().unwrap(), None);
+    }
+
+    #[test]
+    fn varints() {
+        let bytes = [0x96, 0x01, 0x7f, 0x80, 0x7f, 0x03, 0x80];
+        let mut reader = ByteLookahead::new(&bytes[..]);
+        assert_eq!(reader.read_varint().unwrap(), Some(150));
+        assert_eq!(reader.peek_varint_signed().unwrap(), Some(-1));
+        assert_eq!(reader.read_varint().unwrap(), Some(127));
+        assert_eq!(reader.read_varint_signed().unwrap(), Some(-128));
+        assert_eq!(reader.read_varint_zigzag().unwrap(), Some(-2));
+        assert_eq!(reader.read_varint().unwrap(), None);
+        assert_eq!(reader.read_u8().unwrap(), Some(0x80));
+    }
+
+    #[test]
+    fn varint_overflow() {
+        let mut bytes = [0xff; 10];
+        bytes[9] = 0x01;
+        let mut reader = ByteLookahead::new(&bytes[..]);
+        assert_eq!(reader.peek_varint().unwrap(), Some(u64::MAX));
+        bytes[9] = 0x02;
+        let mut reader = ByteLookahead::new(&bytes[..]);
+        let error = reader.read_varint().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let mut bytes = [0x80; 10];
+        bytes[9] = 0x7f;
+        let mut reader = ByteLookahead::new(&bytes[..]);
+        assert_eq!(reader.peek_varint_signed().unwrap(), Some(i64::MIN));
+        assert!(reader.peek_varint().is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::iter::FusedIterator;
+
+/// A location in a source text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    /// Return the byte offset from the start of the source.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Return the line number, starting at `1`.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Return the column number in chars, starting at `1`.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// An iterator over the chars of a string with arbitrary lookahead that tracks where each char
+/// is located.
+///
+/// Lines are separated by `\n`. If CRLF normalization is enabled, each `\r\n` is yielded as a
+/// single `\n` located at the `\r`.
+#[derive(Clone, Debug)]
+pub struct CharLookahead<'a> {
+    source: &'a str,
+    queue: VecDeque<(char, Location)>,
+    end: Location,
+    normalize_crlf: bool,
+}
+
+impl<'a> CharLookahead<'a> {
+    /// Create a [`CharLookahead`] iterator over the chars of `source`.
+    pub fn new(source: &'a str) -> Self {
+        CharLookahead {
+            source,
+            queue: VecDeque::new(),
+            end: Location {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            normalize_crlf: false,
+        }
+    }
+
+    /// Yield each `\r\n` as a single `\n` if `normalize` is `true`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::CharLookahead;
+    ///
+    /// let mut iter = CharLookahead::new("a\r\nb").normalize_crlf(true);
+    ///
+    /// assert_eq!(iter.lookahead(1), Some('\n'));
+    /// assert_eq!(iter.location_of(2).offset(), 3);
+    /// assert_eq!(iter.location_of(2).line(), 2);
+    /// ```
+    pub fn normalize_crlf(mut self, normalize: bool) -> Self {
+        self.normalize_crlf = normalize;
+        self
+    }
+
+    /// Return the char `n` iterations ahead without advancing the iterator.
+    pub fn lookahead(&mut self, n: usize) -> Option<char> {
+        self.fill(n);
+        self.queue.get(n).map(|&(c, _)| c)
+    }
+
+    /// Return the location of the char `n` iterations ahead, or of the end of the source if
+    /// there are fewer chars left.
+    pub fn location_of(&mut self, n: usize) -> Location {
+        self.fill(n);
+        match self.queue.get(n) {
+            Some(&(_, location)) => location,
+            None => self.end,
+        }
+    }
+
+    /// Return the location of the next char, or of the end of the source if there is none.
+    pub fn location(&mut self) -> Location {
+        self.location_of(0)
+    }
+
+    /// Return the part of the source that has not been consumed.
+    pub fn as_str(&self) -> &'a str {
+        let offset = match self.queue.front() {
+            Some(&(_, location)) => location.offset,
+            None => self.end.offset,
+        };
+        &self.source[offset..]
+    }
+
+    /// Return the part of the source that holds the next `len` chars, without advancing the
+    /// iterator.
+    ///
+    /// The slice is taken from the source as is, so any `\r\n` in it is not normalized.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::CharLookahead;
+    ///
+    /// let mut iter = CharLookahead::new("fn main");
+    ///
+    /// assert_eq!(iter.peek_str(2), "fn");
+    /// assert_eq!(iter.peek_str(100), "fn main");
+    /// ```
+    pub fn peek_str(&mut self, len: usize) -> &'a str {
+        let start = self.location().offset;
+        let end = self.location_of(len).offset;
+        &self.source[start..end]
+    }
+
+    /// Return `true` if the upcoming chars are those of `pattern`, without advancing the
+    /// iterator.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::CharLookahead;
+    ///
+    /// let mut iter = CharLookahead::new("while true");
+    ///
+    /// assert!(iter.starts_with("while"));
+    /// assert!(!iter.starts_with("whilst"));
+    /// ```
+    pub fn starts_with(&mut self, pattern: &str) -> bool {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.lookahead(i) == Some(c))
+    }
+
+    /// Buffer chars until the one `n` iterations ahead, or until the end of the source.
+    fn fill(&mut self, n: usize) {
+        while self.queue.len() <= n {
+            let rest = &self.source[self.end.offset..];
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => break,
+            };
+            let len = if self.normalize_crlf && rest.starts_with("\r\n") {
+                2
+            } else {
+                c.len_utf8()
+            };
+            let c = if len == 2 && c == '\r' { '\n' } else { c };
+            self.queue.push_back((c, self.end));
+            self.end.offset += len;
+            if c == '\n' {
+                self.end.line += 1;
+                self.end.column = 1;
+            } else {
+                self.end.column += 1;
+            }
+        }
+    }
+}
+
+impl Iterator for CharLookahead<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill(0);
+        self.queue.pop_front().map(|(c, _)| c)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rest = self.source.len() - self.end.offset;
+        let queued = self.queue.len();
+        // Each char takes up at most four bytes.
+        let lower = rest.saturating_add(3) / 4;
+        (lower + queued, Some(rest + queued))
+    }
+}
+
+impl FusedIterator for CharLookahead<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn locations() {
+        let mut iter = CharLookahead::new("ab\nλ\r\nc");
+        assert_eq!(iter.lookahead(3), Some('λ'));
+        let location = iter.location_of(3);
+        assert_eq!(
+            (location.offset(), location.line(), location.column()),
+            (3, 2, 1)
+        );
+        assert_eq!(iter.lookahead(4), Some('\r'));
+        assert_eq!(iter.location_of(6).line(), 3);
+        assert_eq!(iter.location_of(6).offset(), 7);
+        assert_eq!(iter.location_of(7).offset(), 8);
+        assert_eq!(iter.nth(2), Some('\n'));
+        assert_eq!(iter.location().to_string(), "2:1");
+    }
+
+    #[test]
+    fn normalize_crlf() {
+        let mut iter = CharLookahead::new("a\r\n\r\rb").normalize_crlf(true);
+        assert_eq!(iter.by_ref().take(3).collect::<String>(), "a\n\r");
+        assert_eq!(iter.location().offset(), 4);
+        assert_eq!(iter.location().column(), 2);
+        assert_eq!(iter.as_str(), "\rb");
+    }
+
+    #[test]
+    fn peek_str() {
+        let mut iter = CharLookahead::new("let x\r\n= 1").normalize_crlf(true);
+        assert!(iter.starts_with("let"));
+        iter.nth(3);
+        assert!(iter.starts_with("x\n="));
+        assert_eq!(iter.peek_str(3), "x\r\n=");
+        assert_eq!(iter.as_str(), "x\r\n= 1");
+        assert!(!iter.starts_with("x\n= 10"));
+    }
+}
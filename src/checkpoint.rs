@@ -0,0 +1,167 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::mem;
+
+use crate::history::History;
+
+/// A saved position in a [`Lookahead`] iterator.
+///
+/// A checkpoint is created by [`Lookahead::mark`] and must be handed back to either
+/// [`Lookahead::reset`] or [`Lookahead::commit`] on the same iterator. Until then, every item
+/// consumed after the checkpoint is kept so that it can be replayed.
+///
+/// [`Lookahead`]: crate::Lookahead
+/// [`Lookahead::mark`]: crate::Lookahead::mark
+/// [`Lookahead::reset`]: crate::Lookahead::reset
+/// [`Lookahead::commit`]: crate::Lookahead::commit
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "a checkpoint keeps consumed items buffered until it is reset or committed"]
+pub struct Checkpoint {
+    brand: usize,
+    position: usize,
+}
+
+impl Checkpoint {
+    /// Return the number of items that had been consumed when the checkpoint was created.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// The items consumed since the earliest live [`Checkpoint`], and the history as it was
+/// before them.
+///
+/// Checkpoints are branded with the address of an allocation owned by the replay. This avoids
+/// a global counter, which would need atomic read-modify-write operations that some `no_std`
+/// targets lack. A replay dropped while checkpoints are live leaks the allocation, so that its
+/// address is never reused while a checkpoint may still carry it.
+pub(crate) struct Replay<T> {
+    items: VecDeque<T>,
+    base: usize,
+    marks: usize,
+    history: History<T>,
+    brand: Option<Box<u8>>,
+    clone: Option<fn(&T) -> T>,
+}
+
+impl<T> Replay<T> {
+    pub(crate) fn new() -> Self {
+        Replay {
+            items: VecDeque::new(),
+            base: 0,
+            marks: 0,
+            history: History::disabled(),
+            brand: None,
+            clone: None,
+        }
+    }
+
+    /// Create a new checkpoint at `position`, where `history` is the current history.
+    pub(crate) fn mark(&mut self, position: usize, history: &History<T>) -> Checkpoint
+    where
+        T: Clone,
+    {
+        let brand = self.brand.get_or_insert_with(|| Box::new(0));
+        let brand = &**brand as *const u8 as usize;
+        if self.marks == 0 {
+            self.base = position;
+            self.history = history.clone();
+            self.clone = Some(T::clone);
+        }
+        self.marks += 1;
+        Checkpoint { brand, position }
+    }
+
+    /// Return `true` if a checkpoint is live, so that consumed items are recorded.
+    pub(crate) fn is_recording(&self) -> bool {
+        self.marks > 0
+    }
+
+    /// Return a copy of `item` if it would be recorded.
+    pub(crate) fn copy(&self, item: &T) -> Option<T> {
+        match self.clone {
+            Some(clone) if self.marks > 0 => Some(clone(item)),
+            _ => None,
+        }
+    }
+
+    /// Record a copy of `item` as consumed.
+    pub(crate) fn record(&mut self, item: &T) {
+        if let Some(copy) = self.copy(item) {
+            self.items.push_back(copy);
+        }
+    }
+
+    /// Release `checkpoint` and return the items consumed after it, oldest first.
+    ///
+    /// `history` is restored to what it was when the checkpoint was created.
+    pub(crate) fn rewind(
+        &mut self,
+        checkpoint: Checkpoint,
+        history: &mut History<T>,
+    ) -> VecDeque<T> {
+        self.check(&checkpoint);
+        let offset = checkpoint.position - self.base;
+        assert!(
+            offset <= self.items.len(),
+            "checkpoint was invalidated by an earlier reset"
+        );
+        let items = self.items.split_off(offset);
+        history.restore(&self.history, &self.items);
+        self.release();
+        items
+    }
+
+    /// Release `checkpoint` without rewinding.
+    pub(crate) fn commit(&mut self, checkpoint: Checkpoint) {
+        self.check(&checkpoint);
+        self.release();
+    }
+
+    fn check(&self, checkpoint: &Checkpoint) {
+        let brand = self
+            .brand
+            .as_ref()
+            .map(|brand| &**brand as *const u8 as usize);
+        assert!(
+            self.marks > 0 && Some(checkpoint.brand) == brand,
+            "checkpoint belongs to a different Lookahead"
+        );
+    }
+
+    fn release(&mut self) {
+        self.marks -= 1;
+        if self.marks == 0 {
+            self.items.clear();
+            self.history = History::disabled();
+        }
+    }
+}
+
+impl<T> Drop for Replay<T> {
+    fn drop(&mut self) {
+        if self.marks > 0 {
+            mem::forget(self.brand.take());
+        }
+    }
+}
+
+/// Checkpoints are tied to the iterator that created them, so a clone starts without any.
+impl<T> Clone for Replay<T> {
+    fn clone(&self) -> Self {
+        Replay::new()
+    }
+}
+
+impl<T> fmt::Debug for Replay<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replay")
+            .field("items", &self.items)
+            .field("marks", &self.marks)
+            .finish()
+    }
+}
@@ -0,0 +1,117 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::panic::Location;
+
+/// The error returned when the next item of a [`Lookahead`] is not what was expected.
+///
+/// See [`Lookahead::expect`].
+///
+/// [`Lookahead`]: crate::Lookahead
+/// [`Lookahead::expect`]: crate::Lookahead::expect
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpectError<T> {
+    found: Option<T>,
+    expected: Vec<String>,
+    position: usize,
+    snippet: String,
+    caller: &'static Location<'static>,
+}
+
+impl<T> ExpectError<T> {
+    pub(crate) fn new(
+        found: Option<T>,
+        expected: String,
+        position: usize,
+        snippet: String,
+        caller: &'static Location<'static>,
+    ) -> Self {
+        ExpectError {
+            found,
+            expected: alloc::vec![expected],
+            position,
+            snippet,
+            caller,
+        }
+    }
+
+    /// Return the item that was found instead, or `None` at the end of the input.
+    pub fn found(&self) -> Option<&T> {
+        self.found.as_ref()
+    }
+
+    /// Return descriptions of what was expected instead.
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+
+    /// Return the number of items consumed before the unexpected item.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Return a rendering of the items around the unexpected item, with it underlined.
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+
+    /// Return the location of the code that expected something else.
+    pub fn caller(&self) -> &'static Location<'static> {
+        self.caller
+    }
+
+    /// Combine two errors for alternatives tried at the same item, so that either was expected.
+    ///
+    /// If the errors occurred at different positions, the one furthest into the input is
+    /// kept, since it got further before failing.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(";".chars());
+    ///
+    /// let digit = iter.expect(char::is_ascii_digit, "a digit").unwrap_err();
+    /// let letter = iter.expect(char::is_ascii_alphabetic, "a letter").unwrap_err();
+    ///
+    /// let error = digit.merge(letter);
+    /// assert_eq!(error.to_string(), "expected a digit or a letter, found ';' at position 0");
+    /// ```
+    pub fn merge(mut self, mut other: Self) -> Self {
+        if other.position > self.position {
+            return other;
+        }
+        if other.position == self.position {
+            for expected in other.expected.drain(..) {
+                if !self.expected.contains(&expected) {
+                    self.expected.push(expected);
+                }
+            }
+        }
+        self
+    }
+}
+
+impl<T: fmt::Debug> fmt::Display for ExpectError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("expected ")?;
+        for (i, expected) in self.expected.iter().enumerate() {
+            if i > 0 {
+                let last = i + 1 == self.expected.len();
+                f.write_str(if last { " or " } else { ", " })?;
+            }
+            f.write_str(expected)?;
+        }
+        match &self.found {
+            Some(found) => write!(f, ", found {:?}", found)?,
+            None => f.write_str(", found end of input")?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> std::error::Error for ExpectError<T> {}
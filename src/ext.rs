@@ -0,0 +1,32 @@
+use crate::Lookahead;
+
+/// An extension trait that adds [`Lookahead`] construction to every [`Iterator`].
+pub trait LookaheadExt: Iterator + Sized {
+    /// Create a [`Lookahead`] iterator over this iterator, the way `.peekable()` creates a
+    /// `Peekable`.
+    ///
+    /// This is not named `lookahead`, since that would shadow [`Lookahead::lookahead`] whenever
+    /// the trait is in scope.
+    ///
+    /// Calling this on a `&mut Lookahead` buffers its items a second time, and items that have
+    /// been looked at but not consumed are lost when the new [`Lookahead`] is dropped. Use
+    /// [`Lookahead::reborrow`] instead, which shares the buffer, position, history and
+    /// checkpoints of the existing [`Lookahead`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::LookaheadExt;
+    ///
+    /// let mut iter = "abc".chars().into_lookahead();
+    ///
+    /// assert_eq!(iter.lookahead(2), Some(&'c'));
+    /// ```
+    fn into_lookahead(self) -> Lookahead<Self> {
+        Lookahead::new(self)
+    }
+}
+
+impl<I> LookaheadExt for I where I: Iterator {}
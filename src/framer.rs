@@ -0,0 +1,344 @@
+use std::io::{self, BufRead, Read};
+
+use crate::bytes::ByteLookahead;
+
+/// The largest frame a [`Framer`] accepts, unless specified otherwise.
+const DEFAULT_MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// The byte order of a length prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// The least significant byte comes first.
+    Little,
+    /// The most significant byte comes first.
+    Big,
+}
+
+/// How a byte stream is divided into frames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// Each frame is preceded by its length in bytes, stored in `width` bytes.
+    LengthPrefix { width: usize, order: ByteOrder },
+    /// Each frame is followed by the given delimiter, such as `\r\n` or a NUL byte.
+    Delimiter(Vec<u8>),
+    /// Each frame is preceded by the given magic marker and its length in bytes, stored in
+    /// `width` bytes.
+    ///
+    /// Bytes that do not start with the marker, or whose length exceeds the maximum frame size,
+    /// are treated as corrupt and skipped until the next marker is found.
+    Magic {
+        magic: Vec<u8>,
+        width: usize,
+        order: ByteOrder,
+    },
+}
+
+/// An iterator over the frames in a byte stream.
+///
+/// Frames are yielded without their length prefix, delimiter or magic marker. Bytes are only
+/// consumed once a complete frame is available, so a partial frame stays buffered if reading
+/// the rest of it fails, and [`Framer::next_frame`] can be called again to retry.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::{ByteLookahead, Framer, Framing};
+///
+/// let reader = ByteLookahead::new(&b"HELO\r\nQUIT\r\n"[..]);
+/// let mut frames = Framer::new(reader, Framing::Delimiter(b"\r\n".to_vec()));
+///
+/// assert_eq!(frames.next_frame()?, Some(b"HELO".to_vec()));
+/// assert_eq!(frames.next_frame()?, Some(b"QUIT".to_vec()));
+/// assert_eq!(frames.next_frame()?, None);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Framer<R> {
+    reader: ByteLookahead<R>,
+    framing: Framing,
+    max_frame_size: usize,
+    skipped: u64,
+    failed: bool,
+}
+
+impl<R: Read> Framer<R> {
+    /// Create a [`Framer`] that divides the bytes of `reader` into frames.
+    ///
+    /// Panics if a length prefix is wider than 8 bytes or empty, or if a delimiter or magic
+    /// marker is empty.
+    pub fn new(reader: ByteLookahead<R>, framing: Framing) -> Self {
+        match &framing {
+            Framing::LengthPrefix { width, .. } => check_width(*width),
+            Framing::Delimiter(delimiter) => assert!(!delimiter.is_empty(), "empty delimiter"),
+            Framing::Magic { magic, width, .. } => {
+                assert!(!magic.is_empty(), "empty magic marker");
+                check_width(*width);
+            }
+        }
+        Framer {
+            reader,
+            framing,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            skipped: 0,
+            failed: false,
+        }
+    }
+
+    /// Reject frames larger than `max` bytes, instead of the default of 1 MiB.
+    ///
+    /// Oversized frames are an error of kind [`io::ErrorKind::InvalidData`], except with
+    /// [`Framing::Magic`], where they are skipped.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max;
+        self
+    }
+
+    /// Return the number of bytes skipped while searching for a magic marker.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &ByteLookahead<R> {
+        &self.reader
+    }
+
+    /// Unwrap this [`Framer`], returning the underlying reader.
+    pub fn into_inner(self) -> ByteLookahead<R> {
+        self.reader
+    }
+
+    /// Consume and return the next frame.
+    ///
+    /// Returns `None` if the input ends between frames, and an error of kind
+    /// [`io::ErrorKind::UnexpectedEof`] if it ends within a frame.
+    pub fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match &self.framing {
+            Framing::LengthPrefix { width, order } => {
+                let (width, order) = (*width, *order);
+                let header = self.reader.peek_bytes(width)?;
+                if header.is_empty() {
+                    return Ok(None);
+                }
+                if header.len() < width {
+                    return Err(truncated());
+                }
+                let len = decode_len(header, order);
+                if len > self.max_frame_size as u64 {
+                    return Err(oversized(len, self.max_frame_size));
+                }
+                self.take_frame(width, len as usize, 0).map(Some)
+            }
+            Framing::Delimiter(delimiter) => {
+                let delimiter = delimiter.clone();
+                self.next_delimited(&delimiter)
+            }
+            Framing::Magic {
+                magic,
+                width,
+                order,
+            } => {
+                let magic = magic.clone();
+                let (width, order) = (*width, *order);
+                self.next_magic(&magic, width, order)
+            }
+        }
+    }
+
+    fn next_delimited(&mut self, delimiter: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let limit = self.max_frame_size.saturating_add(delimiter.len());
+        let mut n = delimiter.len().max(64).min(limit);
+        let mut from = 0;
+        loop {
+            let bytes = self.reader.peek_bytes(n)?;
+            if let Some(i) = find(&bytes[from..], delimiter) {
+                return self.take_frame(0, from + i, delimiter.len()).map(Some);
+            }
+            if bytes.len() < n {
+                return if bytes.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(truncated())
+                };
+            }
+            if n == limit {
+                return Err(oversized(n as u64, self.max_frame_size));
+            }
+            from = n + 1 - delimiter.len();
+            n = n.saturating_mul(2).min(limit);
+        }
+    }
+
+    fn next_magic(
+        &mut self,
+        magic: &[u8],
+        width: usize,
+        order: ByteOrder,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let offset = magic.len() + width;
+        loop {
+            let header = self.reader.peek_bytes(offset)?;
+            if header.is_empty() {
+                return Ok(None);
+            }
+            let matched = header.len().min(magic.len());
+            if header[..matched] == magic[..matched] {
+                if header.len() < offset {
+                    return Err(truncated());
+                }
+                let len = decode_len(&header[magic.len()..], order);
+                if len <= self.max_frame_size as u64 {
+                    return self.take_frame(offset, len as usize, 0).map(Some);
+                }
+            }
+            self.reader.consume(1);
+            self.skipped += 1;
+        }
+    }
+
+    /// Consume a frame of `len` bytes that starts `offset` bytes ahead and is followed by
+    /// `trailer` bytes, and return it.
+    fn take_frame(&mut self, offset: usize, len: usize, trailer: usize) -> io::Result<Vec<u8>> {
+        let total = offset + len + trailer;
+        let bytes = self.reader.peek_bytes(total)?;
+        if bytes.len() < total {
+            return Err(truncated());
+        }
+        let frame = bytes[offset..offset + len].to_vec();
+        self.reader.consume(total);
+        Ok(frame)
+    }
+}
+
+impl<R: Read> Iterator for Framer<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    /// Consume and return the next frame, stopping after the first error.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.next_frame() {
+            Ok(frame) => frame.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn check_width(width: usize) {
+    assert!(
+        (1..=8).contains(&width),
+        "length prefix width must be between 1 and 8 bytes"
+    );
+}
+
+fn decode_len(bytes: &[u8], order: ByteOrder) -> u64 {
+    let fold = |len: u64, &byte: &u8| len << 8 | u64::from(byte);
+    match order {
+        ByteOrder::Little => bytes.iter().rev().fold(0, fold),
+        ByteOrder::Big => bytes.iter().fold(0, fold),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "input ended within a frame")
+}
+
+fn oversized(len: u64, max: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("frame of {} bytes exceeds the maximum of {}", len, max),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framer(bytes: &[u8], framing: Framing) -> Framer<&[u8]> {
+        Framer::new(ByteLookahead::with_capacity(bytes, 4), framing)
+    }
+
+    #[test]
+    fn length_prefix() {
+        let framing = Framing::LengthPrefix {
+            width: 2,
+            order: ByteOrder::Big,
+        };
+        let mut frames = framer(b"\x00\x03abc\x00\x00\x00\x05ab", framing);
+        assert_eq!(frames.next_frame().unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(frames.next_frame().unwrap(), Some(Vec::new()));
+        let error = frames.next_frame().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(frames.get_ref().buffer(), b"\x00\x05ab");
+    }
+
+    #[test]
+    fn length_prefix_too_large() {
+        let framing = Framing::LengthPrefix {
+            width: 4,
+            order: ByteOrder::Little,
+        };
+        let mut frames = framer(b"\x05\x00\x00\x00hello", framing).max_frame_size(4);
+        let error = frames.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(frames.next().is_none());
+    }
+
+    #[test]
+    fn delimiter() {
+        let long = [b'x'; 100];
+        let mut input = b"one\r\n\r\n".to_vec();
+        input.extend_from_slice(&long);
+        input.extend_from_slice(b"\r\nrest");
+        let mut frames = framer(&input, Framing::Delimiter(b"\r\n".to_vec()));
+        assert_eq!(frames.next_frame().unwrap(), Some(b"one".to_vec()));
+        assert_eq!(frames.next_frame().unwrap(), Some(Vec::new()));
+        assert_eq!(frames.next_frame().unwrap(), Some(long.to_vec()));
+        let error = frames.next_frame().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn delimiter_too_large() {
+        let input = [b'x'; 200];
+        let mut frames = framer(&input, Framing::Delimiter(vec![0])).max_frame_size(100);
+        let error = frames.next_frame().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn magic() {
+        let framing = Framing::Magic {
+            magic: b"MG".to_vec(),
+            width: 1,
+            order: ByteOrder::Big,
+        };
+        let input = b"MG\x02hi--M\x01MG\xffMG\x03bye";
+        let mut frames = framer(input, framing).max_frame_size(16);
+        let frames_read = frames.by_ref().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(frames_read, vec![b"hi".to_vec(), b"bye".to_vec()]);
+        assert_eq!(frames.skipped(), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "width")]
+    fn invalid_width() {
+        let framing = Framing::LengthPrefix {
+            width: 9,
+            order: ByteOrder::Big,
+        };
+        let _ = framer(b"", framing);
+    }
+}
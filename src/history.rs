@@ -0,0 +1,106 @@
+use alloc::collections::VecDeque;
+use core::fmt;
+
+/// A bounded record of the most recently consumed items, newest first.
+///
+/// Recording requires cloning, which is captured as a function pointer when the history is
+/// enabled so that the `Iterator` implementation of [`Lookahead`] needs no `Clone` bound.
+///
+/// [`Lookahead`]: crate::Lookahead
+#[derive(Clone)]
+pub(crate) struct History<T> {
+    items: VecDeque<T>,
+    depth: usize,
+    clone: Option<fn(&T) -> T>,
+}
+
+impl<T> History<T> {
+    /// Create a history that records nothing.
+    pub(crate) fn disabled() -> Self {
+        History {
+            items: VecDeque::new(),
+            depth: 0,
+            clone: None,
+        }
+    }
+
+    /// Create a history that records up to `depth` items.
+    pub(crate) fn with_depth(depth: usize) -> Self
+    where
+        T: Clone,
+    {
+        History {
+            items: VecDeque::with_capacity(depth),
+            depth,
+            clone: Some(T::clone),
+        }
+    }
+
+    /// Record up to `depth` items from now on, forgetting the oldest ones beyond it.
+    pub(crate) fn set_depth(&mut self, depth: usize)
+    where
+        T: Clone,
+    {
+        self.items.truncate(depth);
+        self.depth = depth;
+        self.clone = Some(T::clone);
+    }
+
+    /// Return `true` if consumed items are recorded.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.clone.is_some() && self.depth > 0
+    }
+
+    /// Return a copy of `item` if it would be recorded.
+    pub(crate) fn copy(&self, item: &T) -> Option<T> {
+        match self.clone {
+            Some(clone) if self.depth > 0 => Some(clone(item)),
+            _ => None,
+        }
+    }
+
+    /// Record a copy of `item` as the most recently consumed item.
+    pub(crate) fn record(&mut self, item: &T) {
+        if let Some(copy) = self.copy(item) {
+            self.push(copy);
+        }
+    }
+
+    /// Record `item` as the most recently consumed item.
+    pub(crate) fn push(&mut self, item: T) {
+        if self.items.len() == self.depth {
+            self.items.pop_back();
+        }
+        self.items.push_front(item);
+    }
+
+    /// Replace the recorded items with copies of those in `snapshot`, then record `items`,
+    /// oldest first.
+    pub(crate) fn restore<'a, It>(&mut self, snapshot: &'a History<T>, items: It)
+    where
+        It: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        self.items.clear();
+        for item in snapshot.items.iter().rev().chain(items) {
+            self.record(item);
+        }
+    }
+
+    /// Return the item consumed `n` items ago.
+    pub(crate) fn get(&self, n: usize) -> Option<&T> {
+        self.items.get(n)
+    }
+}
+
+impl<T> fmt::Debug for History<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("History")
+            .field("items", &self.items)
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
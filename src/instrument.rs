@@ -0,0 +1,190 @@
+#[cfg(not(feature = "instrument"))]
+pub(crate) use self::disabled::Instrument;
+#[cfg(feature = "instrument")]
+pub(crate) use self::enabled::Instrument;
+#[cfg(feature = "instrument")]
+pub use self::enabled::Metrics;
+
+#[cfg(feature = "instrument")]
+mod enabled {
+    use alloc::boxed::Box;
+    use alloc::collections::BTreeMap;
+    use core::fmt;
+    use core::panic::Location;
+
+    type Callback<T> = Box<dyn FnMut(&T) + Send + Sync>;
+
+    /// Counters describing how a [`Lookahead`] has been used.
+    ///
+    /// See [`Lookahead::metrics`].
+    ///
+    /// [`Lookahead`]: crate::Lookahead
+    /// [`Lookahead::metrics`]: crate::Lookahead::metrics
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct Metrics {
+        pulls: u64,
+        peak_len: usize,
+        reallocations: u64,
+        depths: BTreeMap<&'static Location<'static>, usize>,
+    }
+
+    impl Metrics {
+        /// Return the number of items taken from the underlying iterator.
+        pub fn pulls(&self) -> u64 {
+            self.pulls
+        }
+
+        /// Return the largest number of items that have been buffered at once.
+        pub fn peak_len(&self) -> usize {
+            self.peak_len
+        }
+
+        /// Return the number of times the buffer has had to grow its storage.
+        ///
+        /// This is only counted for buffers that report their capacity.
+        pub fn reallocations(&self) -> u64 {
+            self.reallocations
+        }
+
+        /// Return the largest number of items looked ahead by the code at `location`.
+        pub fn max_depth(&self, location: &Location<'_>) -> Option<usize> {
+            self.depths
+                .iter()
+                .find(|(site, _)| **site == location)
+                .map(|(_, &depth)| depth)
+        }
+
+        /// Return every location that has looked ahead, with the largest number of items it
+        /// looked ahead, ordered by location.
+        pub fn depths(&self) -> impl Iterator<Item = (&'static Location<'static>, usize)> + '_ {
+            self.depths.iter().map(|(&site, &depth)| (site, depth))
+        }
+    }
+
+    impl fmt::Display for Metrics {
+        /// Write a report of every counter, one per line.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "pulls: {}", self.pulls)?;
+            writeln!(f, "peak buffer length: {}", self.peak_len)?;
+            write!(f, "buffer reallocations: {}", self.reallocations)?;
+            for (site, depth) in self.depths() {
+                write!(f, "\nmax depth at {}: {}", site, depth)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Instrumentation of a [`Lookahead`].
+    ///
+    /// [`Lookahead`]: crate::Lookahead
+    pub(crate) struct Instrument<T> {
+        metrics: Metrics,
+        capacity: Option<usize>,
+        on_pull: Option<Callback<T>>,
+    }
+
+    impl<T> Instrument<T> {
+        pub(crate) fn new() -> Self {
+            Instrument {
+                metrics: Metrics::default(),
+                capacity: None,
+                on_pull: None,
+            }
+        }
+
+        pub(crate) fn metrics(&self) -> &Metrics {
+            &self.metrics
+        }
+
+        pub(crate) fn reset(&mut self) {
+            self.metrics = Metrics::default();
+        }
+
+        pub(crate) fn on_pull(&mut self, func: Callback<T>) {
+            self.on_pull = Some(func);
+        }
+
+        /// Note that `item` was taken from the underlying iterator.
+        pub(crate) fn pulled(&mut self, item: &T) {
+            self.metrics.pulls += 1;
+            if let Some(on_pull) = &mut self.on_pull {
+                on_pull(item);
+            }
+        }
+
+        /// Note the length and capacity of the buffer after it has grown.
+        pub(crate) fn buffered(&mut self, len: usize, capacity: Option<usize>) {
+            self.metrics.peak_len = self.metrics.peak_len.max(len);
+            if let (Some(old), Some(new)) = (self.capacity, capacity) {
+                if new > old {
+                    self.metrics.reallocations += 1;
+                }
+            }
+            self.capacity = capacity;
+        }
+
+        /// Note that the code at `location` looked `n` items ahead.
+        pub(crate) fn looked_ahead(&mut self, n: usize, location: &'static Location<'static>) {
+            let depth = self.metrics.depths.entry(location).or_insert(0);
+            *depth = (*depth).max(n + 1);
+        }
+    }
+
+    impl<T> Clone for Instrument<T> {
+        /// Clone the metrics, but not the callback.
+        fn clone(&self) -> Self {
+            Instrument {
+                metrics: self.metrics.clone(),
+                capacity: None,
+                on_pull: None,
+            }
+        }
+    }
+
+    impl<T> fmt::Debug for Instrument<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Instrument")
+                .field("metrics", &self.metrics)
+                .field("on_pull", &self.on_pull.as_ref().map(|_| ".."))
+                .finish()
+        }
+    }
+}
+
+#[cfg(not(feature = "instrument"))]
+mod disabled {
+    use core::marker::PhantomData;
+    use core::panic::Location;
+
+    /// Instrumentation of a [`Lookahead`], which does nothing unless the `instrument` feature
+    /// is enabled.
+    ///
+    /// [`Lookahead`]: crate::Lookahead
+    #[derive(Debug)]
+    pub(crate) struct Instrument<T> {
+        marker: PhantomData<fn(&T)>,
+    }
+
+    impl<T> Instrument<T> {
+        pub(crate) fn new() -> Self {
+            Instrument {
+                marker: PhantomData,
+            }
+        }
+
+        #[inline]
+        pub(crate) fn pulled(&mut self, _item: &T) {}
+
+        #[inline]
+        pub(crate) fn buffered(&mut self, _len: usize, _capacity: Option<usize>) {}
+
+        #[inline]
+        pub(crate) fn looked_ahead(&mut self, _n: usize, _location: &'static Location<'static>) {}
+    }
+
+    impl<T> Clone for Instrument<T> {
+        fn clone(&self) -> Self {
+            Instrument::new()
+        }
+    }
+}
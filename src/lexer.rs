@@ -0,0 +1,492 @@
+//! Maximal-munch helpers for writing lexers on top of [`CharLookahead`].
+//!
+//! Each helper looks at the upcoming chars and, if they start the kind of token it lexes,
+//! consumes the whole token and returns it with the byte offsets it spans. Otherwise, nothing
+//! is consumed and `None` is returned, so helpers can be tried one after the other.
+//!
+//! # Examples
+//!
+//! Basic usage:
+//!
+//! ```
+//! use lookahead::lexer::{self, Number};
+//! use lookahead::{CharLookahead, Spanned};
+//!
+//! let mut iter = CharLookahead::new("x1 = 2.5e3");
+//!
+//! assert_eq!(lexer::identifier(&mut iter), Some(Spanned::new("x1", 0, 2)));
+//! assert_eq!(lexer::number(&mut iter)?, None);
+//! iter.nth(2);
+//! assert_eq!(lexer::number(&mut iter)?, Some(Spanned::new(Number::Float(2500.0), 5, 10)));
+//! # Ok::<(), lexer::LexError>(())
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::fmt;
+use core::iter::FromIterator;
+
+use crate::chars::{CharLookahead, Location};
+use crate::spanned::Spanned;
+
+/// A numeric literal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    /// A literal without a fractional part or exponent.
+    Integer(u64),
+    /// A literal with a fractional part or exponent.
+    Float(f64),
+}
+
+/// A table of keywords, looked up by [`keyword`].
+#[derive(Clone, Debug)]
+pub struct Keywords<'k, T> {
+    table: BTreeMap<&'k str, T>,
+}
+
+impl<'k, T> Keywords<'k, T> {
+    /// Create an empty keyword table.
+    pub fn new() -> Self {
+        Keywords {
+            table: BTreeMap::new(),
+        }
+    }
+
+    /// Add `word` to the table, returning the value it previously had.
+    pub fn insert(&mut self, word: &'k str, value: T) -> Option<T> {
+        self.table.insert(word, value)
+    }
+
+    /// Return the value of `word`, if it is a keyword.
+    pub fn get(&self, word: &str) -> Option<&T> {
+        self.table.get(word)
+    }
+}
+
+impl<T> Default for Keywords<'_, T> {
+    fn default() -> Self {
+        Keywords::new()
+    }
+}
+
+impl<'k, T> FromIterator<(&'k str, T)> for Keywords<'k, T> {
+    fn from_iter<I: IntoIterator<Item = (&'k str, T)>>(iter: I) -> Self {
+        Keywords {
+            table: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// The error returned when the input is malformed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LexError {
+    kind: LexErrorKind,
+    location: Location,
+}
+
+/// The ways in which the input can be malformed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LexErrorKind {
+    /// A string is missing its closing quote.
+    UnterminatedString,
+    /// A string contains an unknown escape sequence, starting with the given char.
+    InvalidEscape(char),
+    /// A block comment is missing its closing delimiter.
+    UnterminatedComment,
+    /// An integer does not fit in a `u64`.
+    IntegerOverflow,
+}
+
+impl LexError {
+    fn new(kind: LexErrorKind, location: Location) -> Self {
+        LexError { kind, location }
+    }
+
+    /// Return what is wrong with the input.
+    pub fn kind(&self) -> LexErrorKind {
+        self.kind
+    }
+
+    /// Return where the malformed token starts.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexErrorKind::UnterminatedString => f.write_str("unterminated string"),
+            LexErrorKind::InvalidEscape(c) => write!(f, "invalid escape sequence `\\{}`", c),
+            LexErrorKind::UnterminatedComment => f.write_str("unterminated block comment"),
+            LexErrorKind::IntegerOverflow => f.write_str("integer literal is too large"),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LexError {}
+
+/// Consume an identifier, which is a letter or `_` followed by any number of letters, digits
+/// and `_`.
+pub fn identifier<'a>(iter: &mut CharLookahead<'a>) -> Option<Spanned<&'a str>> {
+    match iter.lookahead(0) {
+        Some(c) if is_identifier_start(c) => Some(take_while(iter, is_identifier_continue)),
+        _ => None,
+    }
+}
+
+/// Consume an identifier if it is in `keywords`, and return its value.
+///
+/// An identifier that merely starts with a keyword is not consumed.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::lexer::{self, Keywords};
+/// use lookahead::CharLookahead;
+///
+/// let keywords = vec![("if", 1), ("in", 2)].into_iter().collect::<Keywords<_>>();
+/// let mut iter = CharLookahead::new("inner in");
+///
+/// assert_eq!(lexer::keyword(&mut iter, &keywords), None);
+/// iter.nth(5);
+/// assert_eq!(lexer::keyword(&mut iter, &keywords).map(|k| k.value), Some(2));
+/// ```
+pub fn keyword<T: Clone>(
+    iter: &mut CharLookahead<'_>,
+    keywords: &Keywords<'_, T>,
+) -> Option<Spanned<T>> {
+    match iter.lookahead(0) {
+        Some(c) if is_identifier_start(c) => {}
+        _ => return None,
+    }
+    let mut len = 1;
+    while let Some(c) = iter.lookahead(len) {
+        if !is_identifier_continue(c) {
+            break;
+        }
+        len += 1;
+    }
+    let value = keywords.get(iter.peek_str(len))?.clone();
+    let start = iter.location().offset();
+    skip(iter, len);
+    Some(Spanned::new(value, start, iter.location().offset()))
+}
+
+/// Consume an integer or float literal.
+///
+/// Digits may be separated by `_`. A float has a fractional part, an exponent or both; the
+/// `.` must be followed by a digit and the `e` or `E` by a digit or a sign and a digit, so that
+/// `1.max(2)` and `2em` lex as integers followed by other tokens.
+pub fn number(iter: &mut CharLookahead<'_>) -> Result<Option<Spanned<Number>>, LexError> {
+    match iter.lookahead(0) {
+        Some(c) if c.is_ascii_digit() => {}
+        _ => return Ok(None),
+    }
+    let start = iter.location();
+    let rest = iter.as_str();
+    let mut float = false;
+    take_while(iter, is_digit);
+    if iter.lookahead(0) == Some('.') && matches!(iter.lookahead(1), Some(c) if c.is_ascii_digit())
+    {
+        float = true;
+        skip(iter, 1);
+        take_while(iter, is_digit);
+    }
+    if matches!(iter.lookahead(0), Some('e') | Some('E')) {
+        let sign = usize::from(matches!(iter.lookahead(1), Some('+') | Some('-')));
+        if matches!(iter.lookahead(1 + sign), Some(c) if c.is_ascii_digit()) {
+            float = true;
+            skip(iter, 1 + sign);
+            take_while(iter, is_digit);
+        }
+    }
+    let end = iter.location().offset();
+    let text = rest[..end - start.offset()]
+        .chars()
+        .filter(|&c| c != '_')
+        .collect::<String>();
+    let value = if float {
+        Number::Float(text.parse().expect("float literal is well-formed"))
+    } else {
+        let value = text
+            .parse()
+            .map_err(|_| LexError::new(LexErrorKind::IntegerOverflow, start))?;
+        Number::Integer(value)
+    };
+    Ok(Some(Spanned::new(value, start.offset(), end)))
+}
+
+/// Consume a string delimited by `quote`, and return its contents with escape sequences
+/// decoded.
+///
+/// The supported escape sequences are `\n`, `\r`, `\t`, `\0`, `\\`, `\'`, `\"` and `\u{..}`
+/// with up to six hexadecimal digits.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::lexer;
+/// use lookahead::CharLookahead;
+///
+/// let mut iter = CharLookahead::new(r#""tab\t\u{1F980}""#);
+///
+/// let string = lexer::string(&mut iter, '"')?.unwrap();
+/// assert_eq!(string.value, "tab\t🦀");
+/// assert_eq!((string.start, string.end), (0, 16));
+/// # Ok::<(), lexer::LexError>(())
+/// ```
+pub fn string(
+    iter: &mut CharLookahead<'_>,
+    quote: char,
+) -> Result<Option<Spanned<String>>, LexError> {
+    if iter.lookahead(0) != Some(quote) {
+        return Ok(None);
+    }
+    let start = iter.location();
+    iter.next();
+    let mut value = String::new();
+    loop {
+        let location = iter.location();
+        match iter.next() {
+            Some(c) if c == quote => break,
+            Some('\\') => value.push(escape(iter, start, location)?),
+            Some(c) => value.push(c),
+            None => return Err(LexError::new(LexErrorKind::UnterminatedString, start)),
+        }
+    }
+    Ok(Some(Spanned::new(
+        value,
+        start.offset(),
+        iter.location().offset(),
+    )))
+}
+
+/// Consume a comment that starts with `prefix` and runs until the end of the line, and return
+/// it without the line break.
+pub fn line_comment<'a>(iter: &mut CharLookahead<'a>, prefix: &str) -> Option<Spanned<&'a str>> {
+    if iter.starts_with(prefix) {
+        Some(take_while(iter, |c| c != '\n'))
+    } else {
+        None
+    }
+}
+
+/// Consume a comment delimited by `open` and `close`, and return it including the delimiters.
+///
+/// If `nested` is `true`, each `open` inside the comment must be matched by its own `close`.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::lexer;
+/// use lookahead::CharLookahead;
+///
+/// let mut iter = CharLookahead::new("/* a /* b */ c */ d");
+///
+/// let comment = lexer::block_comment(&mut iter, "/*", "*/", true)?.unwrap();
+/// assert_eq!(comment.value, "/* a /* b */ c */");
+/// # Ok::<(), lexer::LexError>(())
+/// ```
+pub fn block_comment<'a>(
+    iter: &mut CharLookahead<'a>,
+    open: &str,
+    close: &str,
+    nested: bool,
+) -> Result<Option<Spanned<&'a str>>, LexError> {
+    if !iter.starts_with(open) {
+        return Ok(None);
+    }
+    let start = iter.location();
+    let rest = iter.as_str();
+    let mut depth = 0;
+    loop {
+        if depth > 0 && iter.starts_with(close) {
+            skip(iter, close.chars().count());
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        } else if (depth == 0 || nested) && iter.starts_with(open) {
+            skip(iter, open.chars().count());
+            depth += 1;
+        } else if iter.next().is_none() {
+            return Err(LexError::new(LexErrorKind::UnterminatedComment, start));
+        }
+    }
+    let end = iter.location().offset();
+    let comment = &rest[..end - start.offset()];
+    Ok(Some(Spanned::new(comment, start.offset(), end)))
+}
+
+/// Decode the escape sequence after a `\` at `location`, in a string starting at `start`.
+fn escape(
+    iter: &mut CharLookahead<'_>,
+    start: Location,
+    location: Location,
+) -> Result<char, LexError> {
+    let invalid = |c| LexError::new(LexErrorKind::InvalidEscape(c), location);
+    let c = match iter.next() {
+        Some(c) => c,
+        None => return Err(LexError::new(LexErrorKind::UnterminatedString, start)),
+    };
+    match c {
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        't' => Ok('\t'),
+        '0' => Ok('\0'),
+        '\\' | '\'' | '"' => Ok(c),
+        'u' => {
+            if iter.next() != Some('{') {
+                return Err(invalid('u'));
+            }
+            let mut code = 0;
+            let mut digits = 0;
+            loop {
+                match iter.next() {
+                    Some('}') if digits > 0 => break,
+                    Some(c) if digits < 6 && c.is_ascii_hexdigit() => {
+                        code = code * 16 + c.to_digit(16).unwrap();
+                        digits += 1;
+                    }
+                    _ => return Err(invalid('u')),
+                }
+            }
+            core::char::from_u32(code).ok_or_else(|| invalid('u'))
+        }
+        c => Err(invalid(c)),
+    }
+}
+
+/// Consume chars while they satisfy `pred`, and return them.
+fn take_while<'a, P>(iter: &mut CharLookahead<'a>, pred: P) -> Spanned<&'a str>
+where
+    P: Fn(char) -> bool,
+{
+    let start = iter.location().offset();
+    let rest = iter.as_str();
+    while let Some(c) = iter.lookahead(0) {
+        if !pred(c) {
+            break;
+        }
+        iter.next();
+    }
+    let end = iter.location().offset();
+    Spanned::new(&rest[..end - start], start, end)
+}
+
+fn skip(iter: &mut CharLookahead<'_>, n: usize) {
+    for _ in 0..n {
+        iter.next();
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn identifiers() {
+        let mut iter = CharLookahead::new("_föo9 1x");
+        assert_eq!(identifier(&mut iter), Some(Spanned::new("_föo9", 0, 6)));
+        iter.next();
+        assert_eq!(identifier(&mut iter), None);
+        assert_eq!(iter.lookahead(0), Some('1'));
+    }
+
+    #[test]
+    fn numbers() {
+        let mut iter = CharLookahead::new("1_000 1.max 2.5e-1_0 1.2.3 3e 18446744073709551616");
+        let mut lex = |skipped| {
+            skip(&mut iter, skipped);
+            number(&mut iter).map(|n| n.map(|n| n.value))
+        };
+        assert_eq!(lex(0), Ok(Some(Number::Integer(1000))));
+        assert_eq!(lex(1), Ok(Some(Number::Integer(1))));
+        assert_eq!(lex(0), Ok(None));
+        assert_eq!(lex(5), Ok(Some(Number::Float(2.5e-10))));
+        assert_eq!(lex(1), Ok(Some(Number::Float(1.2))));
+        assert_eq!(lex(1), Ok(Some(Number::Integer(3))));
+        assert_eq!(lex(1), Ok(Some(Number::Integer(3))));
+        let error = lex(2).unwrap_err();
+        assert_eq!(error.kind(), LexErrorKind::IntegerOverflow);
+        assert_eq!(error.location().column(), 31);
+    }
+
+    #[test]
+    fn strings() {
+        let mut iter = CharLookahead::new(r#"'it\'s' "a\qb" "\u{110000}" "open"#);
+        let string = string(&mut iter, '\'').unwrap().unwrap();
+        assert_eq!(string, Spanned::new(String::from("it's"), 0, 7));
+        assert_eq!(super::string(&mut iter, '"'), Ok(None));
+        iter.next();
+        let error = super::string(&mut iter, '"').unwrap_err();
+        assert_eq!(error.kind(), LexErrorKind::InvalidEscape('q'));
+        assert_eq!(error.location().offset(), 10);
+        skip(&mut iter, 3);
+        let error = super::string(&mut iter, '"').unwrap_err();
+        assert_eq!(error.kind(), LexErrorKind::InvalidEscape('u'));
+        skip(&mut iter, 2);
+        let error = super::string(&mut iter, '"').unwrap_err();
+        assert_eq!(error.kind(), LexErrorKind::UnterminatedString);
+        assert_eq!(error.to_string(), "1:29: unterminated string");
+    }
+
+    #[test]
+    fn comments() {
+        let mut iter = CharLookahead::new("// note\n/* a /* b */ c */ (* x");
+        assert_eq!(
+            line_comment(&mut iter, "//"),
+            Some(Spanned::new("// note", 0, 7))
+        );
+        assert_eq!(line_comment(&mut iter, "//"), None);
+        iter.next();
+        let comment = block_comment(&mut iter, "/*", "*/", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(comment.value, "/* a /* b */");
+        assert!(iter.starts_with(" c */"));
+        skip(&mut iter, 6);
+        let error = block_comment(&mut iter, "(*", "*)", true).unwrap_err();
+        assert_eq!(error.kind(), LexErrorKind::UnterminatedComment);
+        assert_eq!(error.location().line(), 2);
+    }
+
+    #[test]
+    fn keywords() {
+        let mut keywords = Keywords::new();
+        keywords.insert("fn", 0);
+        keywords.insert("for", 1);
+        let mut iter = CharLookahead::new("for format");
+        assert_eq!(keyword(&mut iter, &keywords), Some(Spanned::new(1, 0, 3)));
+        iter.next();
+        assert_eq!(keyword(&mut iter, &keywords), None);
+        assert_eq!(identifier(&mut iter).map(|i| i.value), Some("format"));
+    }
+}
@@ -1,128 +1,76 @@
-use std::collections::VecDeque;
-use std::iter::Fuse;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[derive(Clone, Debug)]
-pub struct Lookahead<I: Iterator> {
-    iter: Fuse<I>,
-    queue: VecDeque<I::Item>,
-}
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
-impl<I: Iterator> Lookahead<I> {
-    /// Create a [`Lookahead`] iterator over the given iterable.
-    pub fn new<T>(iterable: T) -> Self
-    where
-        T: IntoIterator<IntoIter = I, Item = I::Item>,
-    {
-        Lookahead {
-            iter: iterable.into_iter().fuse(),
-            queue: VecDeque::new(),
-        }
-    }
+mod array;
+#[cfg(feature = "alloc")]
+mod buffer;
+#[cfg(feature = "std")]
+mod bytes;
+#[cfg(feature = "alloc")]
+mod chars;
+#[cfg(feature = "alloc")]
+mod checkpoint;
+#[cfg(feature = "alloc")]
+mod expect;
+#[cfg(feature = "alloc")]
+mod ext;
+#[cfg(feature = "std")]
+mod framer;
+#[cfg(feature = "alloc")]
+mod history;
+#[cfg(feature = "alloc")]
+mod instrument;
+#[cfg(feature = "alloc")]
+pub mod lexer;
+#[cfg(feature = "alloc")]
+mod limits;
+#[cfg(feature = "alloc")]
+mod lookahead;
+#[cfg(feature = "alloc")]
+mod pattern;
+#[cfg(feature = "std")]
+mod prefetch;
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+mod ring;
+mod spanned;
+#[cfg(feature = "alloc")]
+mod stream;
+#[cfg(feature = "alloc")]
+mod trie;
+#[cfg(feature = "alloc")]
+mod try_lookahead;
 
-    /// Create a [`Lookahead`] iterator over the given iterable with the specified capacity.
-    pub fn with_capacity<T>(iterable: T, capacity: usize) -> Self
-    where
-        T: IntoIterator<IntoIter = I, Item = I::Item>,
-    {
-        Lookahead {
-            iter: iterable.into_iter().fuse(),
-            queue: VecDeque::with_capacity(capacity),
-        }
-    }
-
-    /// Return a reference to the item `n` iterations ahead without advancing the iterator.
-    ///
-    /// When `n` is `0`, it is equivalent to [`Peekable::peek`].
-    ///
-    /// [`Peekable::peek`]: https://doc.rust-lang.org/std/iter/struct.Peekable.html#method.peek
-    ///
-    /// # Examples
-    ///
-    /// Basic usage:
-    ///
-    /// ```
-    /// use lookahead::Lookahead;
-    /// 
-    /// let xs = [1, 2, 3];
-    ///
-    /// let inner = xs.into_iter();
-    /// let mut iter = Lookahead::new(inner);
-    ///
-    /// // `.lookahead(0)` peeks at the item that would otherwise have been returned from `.next()`
-    /// assert_eq!(iter.lookahead(0), Some(&&1));
-    ///
-    /// ```
-    pub fn lookahead(&mut self, n: usize) -> Option<&I::Item> {
-        let enqueued = self.queue.len();
-        if n >= enqueued {
-            let iter = &mut self.iter;
-            let items = iter.take(n - enqueued + 1);
-            self.queue.extend(items);
-        }
-        self.queue.get(n)
-    }
-}
-
-impl<I> Iterator for Lookahead<I>
-where
-    I: Iterator,
-{
-    type Item = I::Item;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.queue.pop_front().or_else(|| self.iter.next())
-    }
-
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let queued = self.queue.len();
-        let (lower, upper) = self.iter.size_hint();
-        (lower + queued, upper.map(|n| n + queued))
-    }
-}
-
-impl<I> ExactSizeIterator for Lookahead<I> where I: ExactSizeIterator {}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn zero() {
-        let inner = [1, 2].iter();
-        let mut iter = Lookahead::new(inner);
-        assert_eq!(iter.lookahead(0), Some(&&1));
-    }
-
-    #[test]
-    fn one() {
-        let inner = [1, 2].iter();
-        let mut iter = Lookahead::new(inner);
-        assert_eq!(iter.lookahead(1), Some(&&2));
-    }
-
-    #[test]
-    fn two() {
-        let inner = [1, 2].iter();
-        let mut iter = Lookahead::new(inner);
-        assert_eq!(iter.lookahead(2), None);
-    }
-
-    #[test]
-    fn next() {
-        let inner = [1, 2].iter();
-        let mut iter = Lookahead::new(inner);
-        let _ = iter.next();
-        assert_eq!(iter.lookahead(0), Some(&&2));
-    }
-
-    #[test]
-    fn size_hint() {
-        let inner = [1, 2].iter();
-        let mut iter = Lookahead::new(inner);
-        assert_eq!(iter.size_hint(), (2, Some(2)));
-        let _ = iter.lookahead(1);
-        assert_eq!(iter.size_hint(), (2, Some(2)));
-        let _ = iter.next();
-        assert_eq!(iter.size_hint(), (1, Some(1)));
-    }
-}
+pub use array::{ArrayLookahead, CapacityError};
+#[cfg(feature = "alloc")]
+pub use buffer::{LookaheadBuffer, RingBuffer, SmallBuffer};
+#[cfg(feature = "std")]
+pub use bytes::ByteLookahead;
+#[cfg(feature = "alloc")]
+pub use chars::{CharLookahead, Location};
+#[cfg(feature = "alloc")]
+pub use checkpoint::Checkpoint;
+#[cfg(feature = "alloc")]
+pub use expect::ExpectError;
+#[cfg(feature = "alloc")]
+pub use ext::LookaheadExt;
+#[cfg(feature = "std")]
+pub use framer::{ByteOrder, Framer, Framing};
+#[cfg(feature = "instrument")]
+pub use instrument::Metrics;
+#[cfg(feature = "alloc")]
+pub use limits::{LimitExceeded, Limits};
+#[cfg(feature = "alloc")]
+pub use lookahead::{Drain, Lookahead, Reborrow, Spans, Unbuffered};
+#[cfg(feature = "alloc")]
+pub use pattern::Pattern;
+#[cfg(feature = "std")]
+pub use prefetch::PrefetchLookahead;
+pub use spanned::Spanned;
+#[cfg(feature = "alloc")]
+pub use stream::{LookaheadStream, Stream};
+#[cfg(feature = "alloc")]
+pub use trie::{TrieMatch, TrieSet};
+#[cfg(feature = "alloc")]
+pub use try_lookahead::TryLookahead;
@@ -0,0 +1,150 @@
+use core::fmt;
+
+/// A function returning the weight of an item.
+type Weigher<T> = fn(&T) -> usize;
+
+/// Bounds on how far a [`Lookahead`] may buffer ahead.
+///
+/// By default, nothing is bounded. Limits are set with [`Lookahead::with_limits`] or
+/// [`Lookahead::set_limits`], and looking beyond them makes [`Lookahead::try_lookahead`] fail
+/// and [`Lookahead::lookahead`] return `None`.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::{Limits, Lookahead};
+///
+/// let limits = Limits::new().max_items(64).max_weight(4096, |s: &String| s.len());
+/// let iter = Lookahead::with_limits(vec![String::from("a")], limits);
+/// ```
+///
+/// [`Lookahead`]: crate::Lookahead
+/// [`Lookahead::with_limits`]: crate::Lookahead::with_limits
+/// [`Lookahead::set_limits`]: crate::Lookahead::set_limits
+/// [`Lookahead::try_lookahead`]: crate::Lookahead::try_lookahead
+/// [`Lookahead::lookahead`]: crate::Lookahead::lookahead
+pub struct Limits<T> {
+    max_items: Option<usize>,
+    max_weight: Option<(usize, Weigher<T>)>,
+    scan_budget: Option<usize>,
+}
+
+impl<T> Limits<T> {
+    /// Create a set of limits that bounds nothing.
+    pub fn new() -> Self {
+        Limits {
+            max_items: None,
+            max_weight: None,
+            scan_budget: None,
+        }
+    }
+
+    /// Limit the number of buffered items to `max`.
+    pub fn max_items(mut self, max: usize) -> Self {
+        self.max_items = Some(max);
+        self
+    }
+
+    /// Stop buffering items once the sum of their weights, as given by `weigher`, reaches `max`.
+    ///
+    /// The item that crosses the limit is still buffered, so the total weight may exceed `max`
+    /// by at most the weight of one item.
+    pub fn max_weight(mut self, max: usize, weigher: Weigher<T>) -> Self {
+        self.max_weight = Some((max, weigher));
+        self
+    }
+
+    /// Limit the number of items pulled from the underlying iterator by a single lookahead to
+    /// `budget`.
+    pub fn scan_budget(mut self, budget: usize) -> Self {
+        self.scan_budget = Some(budget);
+        self
+    }
+
+    /// Check that `n` items can be added to the `buffered` items already present.
+    pub(crate) fn check_pull(&self, buffered: usize, n: usize) -> Result<(), LimitExceeded> {
+        match self.scan_budget {
+            Some(budget) if n > budget => return Err(LimitExceeded::ScanBudget(budget)),
+            _ => {}
+        }
+        match self.max_items {
+            Some(max) if buffered.saturating_add(n) > max => Err(LimitExceeded::Items(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Return the weight of `items`, or `None` if no weight limit is set.
+    pub(crate) fn weigh<'a, It>(&self, items: It) -> Option<usize>
+    where
+        It: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        let (_, weigher) = self.max_weight?;
+        Some(items.into_iter().map(weigher).sum())
+    }
+
+    /// Check that more items can be buffered on top of `weight`.
+    pub(crate) fn check_weight(&self, weight: Option<usize>) -> Result<(), LimitExceeded> {
+        match (self.max_weight, weight) {
+            (Some((max, _)), Some(weight)) if weight >= max => Err(LimitExceeded::Weight(max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T> Clone for Limits<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Limits<T> {}
+
+impl<T> fmt::Debug for Limits<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Limits")
+            .field("max_items", &self.max_items)
+            .field("max_weight", &self.max_weight.map(|(max, _)| max))
+            .field("scan_budget", &self.scan_budget)
+            .finish()
+    }
+}
+
+impl<T> Default for Limits<T> {
+    fn default() -> Self {
+        Limits::new()
+    }
+}
+
+/// The error returned when a lookahead would exceed one of the configured [`Limits`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// More items would have to be buffered than the given maximum.
+    Items(usize),
+    /// The buffered items already weigh at least the given maximum.
+    Weight(usize),
+    /// More items would have to be pulled than the given budget allows.
+    ScanBudget(usize),
+    /// The buffer cannot hold any more items.
+    Capacity,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::Items(max) => write!(f, "lookahead exceeds the limit of {} items", max),
+            LimitExceeded::Weight(max) => {
+                write!(f, "lookahead exceeds the weight limit of {}", max)
+            }
+            LimitExceeded::ScanBudget(budget) => {
+                write!(f, "lookahead exceeds the scan budget of {} items", budget)
+            }
+            LimitExceeded::Capacity => f.write_str("lookahead exceeds the buffer capacity"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LimitExceeded {}
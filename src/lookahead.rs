@@ -0,0 +1,2383 @@
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::{Fuse, FusedIterator};
+use core::mem;
+use core::num::NonZeroUsize;
+use core::ops::{Bound, Deref, DerefMut, RangeBounds};
+use core::panic::Location;
+
+use crate::buffer::{LookaheadBuffer, SmallBuffer};
+use crate::checkpoint::{Checkpoint, Replay};
+use crate::expect::ExpectError;
+use crate::history::History;
+use crate::instrument::Instrument;
+#[cfg(feature = "instrument")]
+use crate::instrument::Metrics;
+use crate::limits::{LimitExceeded, Limits};
+use crate::pattern::{Matcher, Pattern};
+use crate::ring::ArrayRing;
+use crate::spanned::Spanned;
+use crate::trie::{TrieMatch, TrieSet};
+
+/// The buffer of a [`Lookahead`] that is not given one, which holds two items inline.
+type DefaultBuffer<T> = SmallBuffer<T, 2>;
+
+#[derive(Clone, Debug)]
+pub struct Lookahead<I: Iterator, B = DefaultBuffer<<I as Iterator>::Item>> {
+    iter: Fuse<I>,
+    queue: B,
+    back: VecDeque<I::Item>,
+    history: History<I::Item>,
+    replay: Replay<I::Item>,
+    limits: Limits<I::Item>,
+    instrument: Instrument<I::Item>,
+    position: usize,
+}
+
+impl<I: Iterator> Lookahead<I> {
+    /// Create a [`Lookahead`] iterator over the given iterable.
+    pub fn new<T>(iterable: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        Lookahead {
+            iter: iterable.into_iter().fuse(),
+            queue: SmallBuffer::new(),
+            back: VecDeque::new(),
+            history: History::disabled(),
+            replay: Replay::new(),
+            limits: Limits::new(),
+            instrument: Instrument::new(),
+            position: 0,
+        }
+    }
+
+    /// Create a [`Lookahead`] iterator over the given iterable with the specified capacity.
+    pub fn with_capacity<T>(iterable: T, capacity: usize) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        Lookahead {
+            iter: iterable.into_iter().fuse(),
+            queue: SmallBuffer::with_capacity(capacity),
+            back: VecDeque::new(),
+            history: History::disabled(),
+            replay: Replay::new(),
+            limits: Limits::new(),
+            instrument: Instrument::new(),
+            position: 0,
+        }
+    }
+
+    /// Create a [`Lookahead`] iterator over the given iterable that remembers the last `depth`
+    /// consumed items.
+    ///
+    /// See [`Lookahead::lookbehind`].
+    pub fn with_history<T>(iterable: T, depth: usize) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = I::Item>,
+        I::Item: Clone,
+    {
+        Lookahead {
+            iter: iterable.into_iter().fuse(),
+            queue: SmallBuffer::new(),
+            back: VecDeque::new(),
+            history: History::with_depth(depth),
+            replay: Replay::new(),
+            limits: Limits::new(),
+            instrument: Instrument::new(),
+            position: 0,
+        }
+    }
+
+    /// Create a [`Lookahead`] iterator over the given iterable that never buffers beyond
+    /// `limits`.
+    ///
+    /// See [`Lookahead::try_lookahead`].
+    pub fn with_limits<T>(iterable: T, limits: Limits<I::Item>) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        let mut iter = Lookahead::new(iterable);
+        iter.limits = limits;
+        iter
+    }
+}
+
+impl<I, B> Lookahead<I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    /// Create a [`Lookahead`] iterator over the given iterable that buffers items in `buffer`.
+    ///
+    /// The other constructors buffer items in a [`SmallBuffer`] that holds two items inline.
+    /// History and limits can be added with [`Lookahead::set_history`] and
+    /// [`Lookahead::set_limits`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{Lookahead, SmallBuffer};
+    ///
+    /// // Looking up to four items ahead never allocates.
+    /// let mut iter = Lookahead::with_buffer(1..=5, SmallBuffer::<_, 4>::new());
+    ///
+    /// assert_eq!(iter.lookahead(3), Some(&4));
+    /// ```
+    pub fn with_buffer<T>(iterable: T, buffer: B) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        Lookahead {
+            iter: iterable.into_iter().fuse(),
+            queue: buffer,
+            back: VecDeque::new(),
+            history: History::disabled(),
+            replay: Replay::new(),
+            limits: Limits::new(),
+            instrument: Instrument::new(),
+            position: 0,
+        }
+    }
+
+    /// Return a reference to the item `n` iterations ahead without advancing the iterator.
+    ///
+    /// When `n` is `0`, it is equivalent to [`Peekable::peek`].
+    ///
+    /// [`Peekable::peek`]: https://doc.rust-lang.org/std/iter/struct.Peekable.html#method.peek
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let xs = [1, 2, 3];
+    ///
+    /// let inner = xs.into_iter();
+    /// let mut iter = Lookahead::new(inner);
+    ///
+    /// // `.lookahead(0)` peeks at the item that would otherwise have been returned from `.next()`
+    /// assert_eq!(iter.lookahead(0), Some(&&1));
+    ///
+    /// ```
+    ///
+    /// If the buffer has a fixed capacity, items beyond it cannot be looked at and `None` is
+    /// returned for them.
+    ///
+    /// Likewise, `None` is returned for items beyond the configured [`Limits`].
+    #[cfg_attr(feature = "instrument", track_caller)]
+    pub fn lookahead(&mut self, n: usize) -> Option<&I::Item> {
+        self.try_lookahead(n).unwrap_or_default()
+    }
+
+    /// Return a reference to the item `n` iterations ahead without advancing the iterator, or
+    /// an error if buffering it would exceed the configured [`Limits`].
+    ///
+    /// Items that are already buffered are always returned. When an error is returned, the
+    /// items up to the limit have still been buffered. A full fixed-capacity buffer is
+    /// reported as [`LimitExceeded::Capacity`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{LimitExceeded, Limits, Lookahead};
+    ///
+    /// let mut iter = Lookahead::with_limits(1..=10, Limits::new().max_items(4));
+    ///
+    /// assert_eq!(iter.try_lookahead(3), Ok(Some(&4)));
+    /// assert_eq!(iter.try_lookahead(4), Err(LimitExceeded::Items(4)));
+    /// ```
+    #[cfg_attr(feature = "instrument", track_caller)]
+    pub fn try_lookahead(&mut self, n: usize) -> Result<Option<&I::Item>, LimitExceeded> {
+        self.instrument.looked_ahead(n, Location::caller());
+        let buffered = self.queue.len();
+        if n >= buffered {
+            let queue = &self.queue;
+            let mut weight = self
+                .limits
+                .weigh((0..buffered).filter_map(|i| queue.get(i)));
+            while self.queue.len() <= n {
+                let pulled = self.queue.len() - buffered;
+                self.limits.check_pull(buffered, pulled + 1)?;
+                self.limits.check_weight(weight)?;
+                if self.queue.is_full() {
+                    return Err(LimitExceeded::Capacity);
+                }
+                match self.pull().or_else(|| self.back.pop_back()) {
+                    Some(item) => {
+                        if let Some(weight) = weight.as_mut() {
+                            *weight += self.limits.weigh(Some(&item)).unwrap_or(0);
+                        }
+                        self.queue.push_back(item).unwrap_or_else(|_| buffer_full());
+                        self.observe_buffer();
+                    }
+                    None => break,
+                }
+            }
+        }
+        Ok(self.queue.get(n))
+    }
+
+    /// Remember the last `depth` consumed items from now on.
+    ///
+    /// Items that are already remembered are kept, up to the new depth. A depth of `0` turns
+    /// the history off. See [`Lookahead::lookbehind`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{Lookahead, SmallBuffer};
+    ///
+    /// let mut iter = Lookahead::with_buffer("ab".chars(), SmallBuffer::<_, 1>::new());
+    /// iter.set_history(1);
+    ///
+    /// iter.next();
+    /// assert_eq!(iter.lookbehind(0), Some(&'a'));
+    /// ```
+    pub fn set_history(&mut self, depth: usize)
+    where
+        I::Item: Clone,
+    {
+        self.history.set_depth(depth);
+    }
+
+    /// Replace the [`Limits`] that bound the lookahead buffer.
+    ///
+    /// Items that are already buffered are kept, even if they exceed the new limits.
+    pub fn set_limits(&mut self, limits: Limits<I::Item>) {
+        self.limits = limits;
+    }
+
+    /// Return a reference to the item consumed `n` iterations ago.
+    ///
+    /// When `n` is `0`, this is the item most recently returned from `.next()`. Only as many
+    /// items as the depth given to [`Lookahead::with_history`] or [`Lookahead::set_history`]
+    /// are remembered; without a history, `None` is always returned.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::with_history("a,b".chars(), 2);
+    ///
+    /// assert_eq!(iter.lookbehind(0), None);
+    ///
+    /// iter.next();
+    /// iter.next();
+    ///
+    /// assert_eq!(iter.lookbehind(0), Some(&','));
+    /// assert_eq!(iter.lookbehind(1), Some(&'a'));
+    /// ```
+    pub fn lookbehind(&self, n: usize) -> Option<&I::Item> {
+        self.history.get(n)
+    }
+
+    /// Return a mutable reference to the item `n` iterations ahead without advancing the iterator.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(vec![1, 2, 3]);
+    ///
+    /// if let Some(x) = iter.lookahead_mut(1) {
+    ///     *x *= 10;
+    /// }
+    ///
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![1, 20, 3]);
+    /// ```
+    #[cfg_attr(feature = "instrument", track_caller)]
+    pub fn lookahead_mut(&mut self, n: usize) -> Option<&mut I::Item> {
+        self.lookahead(n);
+        self.queue.get_mut(n)
+    }
+
+    /// Return a slice of the items in `range` without advancing the iterator.
+    ///
+    /// The range is relative to the next item, so `peek_slice(0..3)` views the three items that
+    /// the next three calls to `.next()` would return. If the iterator runs out before the end of
+    /// the range, or the configured [`Limits`] are reached, the returned slice is shortened
+    /// accordingly. An unbounded end buffers every remaining item.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new("let x".chars());
+    ///
+    /// match iter.peek_slice(0..4) {
+    ///     ['l', 'e', 't', ' '] => {}
+    ///     _ => unreachable!(),
+    /// }
+    ///
+    /// assert_eq!(iter.peek_slice(3..), &[' ', 'x']);
+    /// assert_eq!(iter.peek_slice(4..10), &['x']);
+    /// ```
+    pub fn peek_slice<R>(&mut self, range: R) -> &[I::Item]
+    where
+        R: RangeBounds<usize>,
+    {
+        let (start, end) = self.buffer(range);
+        &self.queue.make_contiguous()[start..end]
+    }
+
+    /// Return references to the items at each of the given offsets without advancing the
+    /// iterator.
+    ///
+    /// Each offset is interpreted as in [`Lookahead::lookahead`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(vec![1, 2, 3]);
+    ///
+    /// assert_eq!(iter.get_many([2, 0, 5]), [Some(&3), Some(&1), None]);
+    /// ```
+    pub fn get_many<const N: usize>(&mut self, offsets: [usize; N]) -> [Option<&I::Item>; N] {
+        if let Some(&max) = offsets.iter().max() {
+            self.lookahead(max);
+        }
+        let mut items = [None; N];
+        for (item, &n) in items.iter_mut().zip(offsets.iter()) {
+            *item = self.queue.get(n);
+        }
+        items
+    }
+
+    /// Consume and return the next `N` items as an array.
+    ///
+    /// If fewer than `N` items remain, `None` is returned and the iterator is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(1..=5);
+    ///
+    /// assert_eq!(iter.next_array(), Some([1, 2, 3]));
+    /// assert_eq!(iter.next_array::<3>(), None);
+    /// assert_eq!(iter.next_array(), Some([4, 5]));
+    /// ```
+    pub fn next_array<const N: usize>(&mut self) -> Option<[I::Item; N]> {
+        let mut items = ArrayRing::<_, N>::new();
+        while !items.is_full() {
+            match self.pop() {
+                Some(item) => {
+                    let _ = items.push_back(item);
+                }
+                None => {
+                    // Everything left has been taken and the underlying iterator is exhausted,
+                    // so the items can be put back behind it regardless of the buffer capacity.
+                    while let Some(item) = items.pop_back() {
+                        self.back.push_back(item);
+                    }
+                    return None;
+                }
+            }
+        }
+        for item in (0..N).filter_map(|i| items.get(i)) {
+            self.record(item);
+        }
+        items.into_array().ok()
+    }
+
+    /// Return an iterator that consumes the next `n` items.
+    ///
+    /// The items are buffered up front, so the returned iterator yields exactly
+    /// `min(n, remaining)` items. Any items that have not been yielded when it is dropped are
+    /// consumed regardless. The configured [`Limits`] do not apply, since the items are
+    /// consumed rather than looked at.
+    ///
+    /// A fixed-capacity buffer cannot hold more than its capacity, so with one, at most that
+    /// many items are yielded.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(1..=5);
+    ///
+    /// assert_eq!(iter.next_n(2).sum::<i32>(), 3);
+    /// assert_eq!(iter.next_n(5).len(), 3);
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn next_n(&mut self, n: usize) -> Drain<'_, I, B> {
+        if n > 0 {
+            self.fill(n - 1);
+        }
+        let remaining = n.min(self.queue.len());
+        Drain {
+            lookahead: self,
+            remaining,
+        }
+    }
+
+    /// Advance the iterator by `n` items, dropping them.
+    ///
+    /// Buffered items are dropped first; the remainder is pulled from the underlying iterator.
+    /// Returns `Err(k)` if the iterator ran out with `k` items left to skip.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    /// use std::num::NonZeroUsize;
+    ///
+    /// let mut iter = Lookahead::new(1..=5);
+    ///
+    /// assert_eq!(iter.advance_by(3), Ok(()));
+    /// assert_eq!(iter.next(), Some(4));
+    /// assert_eq!(iter.advance_by(3), Err(NonZeroUsize::new(2).unwrap()));
+    /// ```
+    pub fn advance_by(&mut self, n: usize) -> Result<(), NonZeroUsize> {
+        if self.history.is_enabled() || self.replay.is_recording() {
+            for i in 0..n {
+                if self.next().is_none() {
+                    return Err(NonZeroUsize::new(n - i).unwrap());
+                }
+            }
+            return Ok(());
+        }
+        let buffered = n.min(self.queue.len());
+        self.queue.drop_front(buffered);
+        let instrument = &mut self.instrument;
+        let pulled = self
+            .iter
+            .by_ref()
+            .take(n - buffered)
+            .inspect(|item| instrument.pulled(item))
+            .count();
+        let mut skipped = buffered + pulled;
+        while skipped < n && self.back.pop_back().is_some() {
+            skipped += 1;
+        }
+        self.position += skipped;
+        match NonZeroUsize::new(n - skipped) {
+            Some(left) => Err(left),
+            None => Ok(()),
+        }
+    }
+
+    /// Return a reference to the next item without advancing the iterator.
+    ///
+    /// Equivalent to `lookahead(0)`.
+    #[cfg_attr(feature = "instrument", track_caller)]
+    pub fn peek(&mut self) -> Option<&I::Item> {
+        self.lookahead(0)
+    }
+
+    /// Return a mutable reference to the next item without advancing the iterator.
+    ///
+    /// Equivalent to `lookahead_mut(0)`.
+    #[cfg_attr(feature = "instrument", track_caller)]
+    pub fn peek_mut(&mut self) -> Option<&mut I::Item> {
+        self.lookahead_mut(0)
+    }
+
+    /// Return `true` if the item `n` iterations ahead exists and satisfies `pred`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new("a=b".chars());
+    ///
+    /// assert!(iter.lookahead_is(1, |&c| c == '='));
+    /// assert!(!iter.lookahead_is(3, |_| true));
+    /// ```
+    #[cfg_attr(feature = "instrument", track_caller)]
+    pub fn lookahead_is<P>(&mut self, n: usize, pred: P) -> bool
+    where
+        P: FnOnce(&I::Item) -> bool,
+    {
+        match self.lookahead(n) {
+            Some(item) => pred(item),
+            None => false,
+        }
+    }
+
+    /// Return the longest sequence in `set` that the upcoming items start with, without
+    /// advancing the iterator.
+    ///
+    /// Only as many items are buffered as are needed to rule out longer sequences.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{Lookahead, TrieSet};
+    ///
+    /// let operators = [">", ">>", ">>="];
+    /// let set = operators.iter().map(|op| op.chars()).collect::<TrieSet<_>>();
+    /// let mut iter = Lookahead::new(">>> 1".chars());
+    ///
+    /// let found = iter.longest_match(&set).unwrap();
+    /// assert_eq!(operators[found.entry()], ">>");
+    /// assert_eq!(found.len(), 2);
+    /// ```
+    pub fn longest_match(&mut self, set: &TrieSet<I::Item>) -> Option<TrieMatch>
+    where
+        I::Item: PartialEq,
+    {
+        let mut node = 0;
+        let mut longest = set.entry(node).map(|entry| TrieMatch::new(entry, 0));
+        let mut n = 0;
+        while let Some(item) = self.lookahead(n) {
+            node = match set.child(node, item) {
+                Some(child) => child,
+                None => break,
+            };
+            n += 1;
+            if let Some(entry) = set.entry(node) {
+                longest = Some(TrieMatch::new(entry, n));
+            }
+        }
+        longest
+    }
+
+    /// Return the number of upcoming items matched by `pattern`, without advancing the
+    /// iterator.
+    ///
+    /// If the pattern can match in several ways, the longest match is returned. Only as many
+    /// items are buffered as are needed to rule out longer matches.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{Lookahead, Pattern};
+    ///
+    /// let digit = || Pattern::pred(char::is_ascii_digit);
+    /// let number = Pattern::seq(vec![
+    ///     digit().plus(),
+    ///     Pattern::seq(vec![Pattern::eq('.'), digit().plus()]).optional(),
+    /// ]);
+    ///
+    /// assert_eq!(Lookahead::new("3.14)".chars()).matches_ahead(&number), Some(4));
+    /// assert_eq!(Lookahead::new("3.)".chars()).matches_ahead(&number), Some(1));
+    /// assert_eq!(Lookahead::new(".5".chars()).matches_ahead(&number), None);
+    /// ```
+    pub fn matches_ahead(&mut self, pattern: &Pattern<'_, I::Item>) -> Option<usize> {
+        let mut matcher = Matcher::new(pattern);
+        let mut n = 0;
+        while matcher.is_alive() {
+            match self.lookahead(n) {
+                Some(item) => matcher.step(item),
+                None => break,
+            }
+            n += 1;
+        }
+        matcher.longest()
+    }
+
+    /// Return `true` if the upcoming items are equal to `items`, without advancing the iterator.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new("..=".chars());
+    ///
+    /// assert!(iter.starts_with(&['.', '.']));
+    /// assert!(!iter.starts_with(&['.', '=']));
+    /// ```
+    pub fn starts_with(&mut self, items: &[I::Item]) -> bool
+    where
+        I::Item: PartialEq,
+    {
+        items
+            .iter()
+            .enumerate()
+            .all(|(n, item)| self.lookahead(n) == Some(item))
+    }
+
+    /// Consume and return the upcoming items if they are equal to `items`.
+    ///
+    /// Otherwise, the iterator is left unchanged and `None` is returned.
+    pub fn expect_seq(&mut self, items: &[I::Item]) -> Option<Vec<I::Item>>
+    where
+        I::Item: PartialEq,
+    {
+        if self.starts_with(items) {
+            Some(self.next_n(items.len()).collect())
+        } else {
+            None
+        }
+    }
+
+    /// Consume and return the next item if it satisfies `func`.
+    ///
+    /// Otherwise, the iterator is left unchanged and `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(0..5);
+    ///
+    /// assert_eq!(iter.next_if(|&x| x == 0), Some(0));
+    /// assert_eq!(iter.next_if(|&x| x == 0), None);
+    /// assert_eq!(iter.next(), Some(1));
+    /// ```
+    pub fn next_if<F>(&mut self, func: F) -> Option<I::Item>
+    where
+        F: FnOnce(&I::Item) -> bool,
+    {
+        if self.lookahead_is(0, func) {
+            self.next()
+        } else {
+            None
+        }
+    }
+
+    /// Consume and return the next item if it is equal to `expected`.
+    pub fn next_if_eq<T>(&mut self, expected: &T) -> Option<I::Item>
+    where
+        T: ?Sized,
+        I::Item: PartialEq<T>,
+    {
+        self.next_if(|item| item == expected)
+    }
+
+    /// Consume and return the next item if it satisfies `func`, or return an error describing
+    /// what was expected instead.
+    ///
+    /// The error records the caller's location, and renders a snippet of the buffered items
+    /// around the unexpected one, including any remembered by [`Lookahead::with_history`] or
+    /// [`Lookahead::set_history`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::with_history("(1;2)".chars(), 2);
+    ///
+    /// assert_eq!(iter.expect_eq(&'(', "`(`"), Ok('('));
+    /// assert_eq!(iter.expect(char::is_ascii_digit, "a digit"), Ok('1'));
+    ///
+    /// let error = iter.expect_eq(&',', "`,`").unwrap_err();
+    /// assert_eq!(error.found(), Some(&';'));
+    /// assert_eq!(error.to_string(), "expected `,`, found ';' at position 2");
+    /// assert_eq!(error.snippet(), "'(' '1' ';'\n        ^^^");
+    /// ```
+    #[track_caller]
+    pub fn expect<F>(&mut self, func: F, description: &str) -> Result<I::Item, ExpectError<I::Item>>
+    where
+        F: FnOnce(&I::Item) -> bool,
+        I::Item: Clone + fmt::Debug,
+    {
+        match self.next_if(func) {
+            Some(item) => Ok(item),
+            None => Err(self.unexpected(description)),
+        }
+    }
+
+    /// Consume and return the next item if it is equal to `expected`, or return an error
+    /// describing what was expected instead.
+    ///
+    /// See [`Lookahead::expect`].
+    #[track_caller]
+    pub fn expect_eq<T>(
+        &mut self,
+        expected: &T,
+        description: &str,
+    ) -> Result<I::Item, ExpectError<I::Item>>
+    where
+        T: ?Sized,
+        I::Item: PartialEq<T> + Clone + fmt::Debug,
+    {
+        self.expect(|item| item == expected, description)
+    }
+
+    /// Return an error if there are any items left.
+    ///
+    /// See [`Lookahead::expect`].
+    #[track_caller]
+    pub fn expect_end(&mut self) -> Result<(), ExpectError<I::Item>>
+    where
+        I::Item: Clone + fmt::Debug,
+    {
+        match self.lookahead(0) {
+            Some(_) => Err(self.unexpected("end of input")),
+            None => Ok(()),
+        }
+    }
+
+    /// Consume the next item and return the result of `func` if it is `Ok`.
+    ///
+    /// If `func` returns `Err`, the item it carries is put back in front of the iterator.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new("4x".chars());
+    ///
+    /// let digit = |c: char| c.to_digit(10).ok_or(c);
+    ///
+    /// assert_eq!(iter.next_if_map(digit), Some(4));
+    /// assert_eq!(iter.next_if_map(digit), None);
+    /// assert_eq!(iter.next(), Some('x'));
+    /// ```
+    pub fn next_if_map<R, F>(&mut self, func: F) -> Option<R>
+    where
+        F: FnOnce(I::Item) -> Result<R, I::Item>,
+    {
+        let item = self.pop()?;
+        let copy = self.history.copy(&item).or_else(|| self.replay.copy(&item));
+        match func(item) {
+            Ok(result) => {
+                match copy {
+                    Some(copy) => self.record(&copy),
+                    None => self.position += 1,
+                }
+                Some(result)
+            }
+            Err(item) => {
+                self.put_back(item);
+                None
+            }
+        }
+    }
+
+    /// Put `item` back in front of the iterator, so that it is returned by the next call to
+    /// `.next()`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(vec![2, 3]);
+    ///
+    /// iter.put_back(1);
+    ///
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![1, 2, 3]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is full.
+    pub fn put_back(&mut self, item: I::Item) {
+        self.queue
+            .push_front(item)
+            .unwrap_or_else(|_| buffer_full());
+        self.observe_buffer();
+    }
+
+    /// Insert `item` so that it becomes the item `n` iterations ahead.
+    ///
+    /// The configured [`Limits`] do not apply.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `n` items remain, or if the buffer is full.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(vec![1, 3]);
+    ///
+    /// iter.insert(1, 2);
+    /// iter.insert(3, 4);
+    ///
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    /// ```
+    pub fn insert(&mut self, n: usize, item: I::Item) {
+        self.buffer_for_edit(n);
+        assert!(n <= self.queue.len(), "insertion index out of bounds");
+        self.queue.insert(n, item).unwrap_or_else(|_| buffer_full());
+        self.observe_buffer();
+    }
+
+    /// Remove and return the item `n` iterations ahead, without advancing past the items before
+    /// it.
+    ///
+    /// The configured [`Limits`] do not apply.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is full before reaching the item.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(vec![1, 2, 3]);
+    ///
+    /// assert_eq!(iter.remove(1), Some(2));
+    /// assert_eq!(iter.remove(2), None);
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![1, 3]);
+    /// ```
+    pub fn remove(&mut self, n: usize) -> Option<I::Item> {
+        self.buffer_for_edit(n.saturating_add(1));
+        self.queue.remove(n)
+    }
+
+    /// Replace the items in `range` with `replacement`, returning the removed items.
+    ///
+    /// The range is interpreted as in [`Lookahead::peek_slice`], so it is shortened if the
+    /// iterator runs out before its end. The configured [`Limits`] do not apply.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(vec!["a", "+=", "b"]);
+    ///
+    /// let removed = iter.splice(1..2, vec!["=", "a", "+"]);
+    ///
+    /// assert_eq!(removed, vec!["+="]);
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec!["a", "=", "a", "+", "b"]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer cannot hold the range or the replacement.
+    pub fn splice<R, T>(&mut self, range: R, replacement: T) -> Vec<I::Item>
+    where
+        R: RangeBounds<usize>,
+        T: IntoIterator<Item = I::Item>,
+    {
+        let (start, end) = bounds(range);
+        self.buffer_for_edit(end);
+        let end = end.min(self.queue.len());
+        let start = start.min(end);
+        let removed = (start..end)
+            .filter_map(|_| self.queue.remove(start))
+            .collect();
+        for (i, item) in replacement.into_iter().enumerate() {
+            self.queue
+                .insert(start + i, item)
+                .unwrap_or_else(|_| buffer_full());
+        }
+        self.observe_buffer();
+        removed
+    }
+
+    /// Call `func` with every item taken from the underlying iterator.
+    ///
+    /// Items are passed to `func` as they are buffered or consumed, whichever happens first.
+    /// Clones of the iterator do not call `func`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let pulled = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&pulled);
+    ///
+    /// let mut iter = Lookahead::new(1..=3);
+    /// iter.on_pull(move |_| {
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    /// });
+    ///
+    /// iter.lookahead(1);
+    /// assert_eq!(pulled.load(Ordering::Relaxed), 2);
+    /// ```
+    #[cfg(feature = "instrument")]
+    pub fn on_pull<F>(&mut self, func: F)
+    where
+        F: FnMut(&I::Item) + Send + Sync + 'static,
+    {
+        self.instrument.on_pull(alloc::boxed::Box::new(func));
+    }
+
+    /// Return the counters collected since the iterator was created or its metrics were last
+    /// reset.
+    ///
+    /// The lookahead depth is recorded for each place that calls [`Lookahead::lookahead`],
+    /// [`Lookahead::try_lookahead`], [`Lookahead::lookahead_mut`], [`Lookahead::peek`],
+    /// [`Lookahead::peek_mut`] or [`Lookahead::lookahead_is`]. Other methods that look ahead
+    /// are recorded as a location inside this crate.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(1..=10);
+    ///
+    /// iter.lookahead(4);
+    /// iter.next();
+    ///
+    /// let metrics = iter.metrics();
+    /// assert_eq!(metrics.pulls(), 5);
+    /// assert_eq!(metrics.peak_len(), 5);
+    ///
+    /// let report = metrics.to_string();
+    /// assert!(report.starts_with("pulls: 5\npeak buffer length: 5\n"));
+    /// assert!(report.lines().last().unwrap().ends_with(": 5"));
+    /// ```
+    #[cfg(feature = "instrument")]
+    pub fn metrics(&self) -> &Metrics {
+        self.instrument.metrics()
+    }
+
+    /// Reset every counter returned by [`Lookahead::metrics`] to zero.
+    #[cfg(feature = "instrument")]
+    pub fn reset_metrics(&mut self) {
+        self.instrument.reset();
+    }
+
+    /// Return the number of items consumed from the front of the iterator.
+    ///
+    /// Resetting to a checkpoint also restores its position.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new("abc".chars());
+    ///
+    /// iter.next();
+    /// assert_eq!(iter.position(), 1);
+    /// assert_eq!(iter.position_of(1), 2);
+    /// ```
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Return the position of the item `n` iterations ahead, which is the value of
+    /// [`Lookahead::position`] just before it is consumed.
+    pub fn position_of(&self, n: usize) -> usize {
+        self.position + n
+    }
+
+    /// Consume and return the next item together with the positions before and after it.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{Lookahead, Spanned};
+    ///
+    /// let mut iter = Lookahead::new(vec!["let", "x"]);
+    ///
+    /// iter.next();
+    /// assert_eq!(iter.next_spanned(), Some(Spanned::new("x", 1, 2)));
+    /// ```
+    pub fn next_spanned(&mut self) -> Option<Spanned<I::Item>> {
+        let start = self.position;
+        let value = self.next()?;
+        Some(Spanned::new(value, start, self.position))
+    }
+
+    /// Return an iterator that consumes the remaining items together with their positions.
+    ///
+    /// The iterator borrows the [`Lookahead`], so iteration can continue on it afterwards.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{Lookahead, Spanned};
+    ///
+    /// let mut iter = Lookahead::new(vec!["let", "x", "=", "1"]);
+    ///
+    /// iter.next();
+    /// let mut spans = iter.spanned();
+    /// assert_eq!(spans.next(), Some(Spanned::new("x", 1, 2)));
+    /// assert_eq!(spans.next(), Some(Spanned::new("=", 2, 3)));
+    /// assert_eq!(iter.next(), Some("1"));
+    /// ```
+    pub fn spanned(&mut self) -> Spans<'_, I, B> {
+        Spans { lookahead: self }
+    }
+
+    /// Save the current position so that the iterator can later be reset to it.
+    ///
+    /// Every item consumed while a checkpoint is live is cloned and kept, so each checkpoint
+    /// must eventually be passed to [`Lookahead::reset`] or [`Lookahead::commit`]. Checkpoints
+    /// may be nested.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(1..=3);
+    ///
+    /// let checkpoint = iter.mark();
+    /// assert_eq!(iter.next(), Some(1));
+    /// assert_eq!(iter.next(), Some(2));
+    ///
+    /// iter.reset(checkpoint);
+    /// assert_eq!(iter.next(), Some(1));
+    /// ```
+    pub fn mark(&mut self) -> Checkpoint
+    where
+        I::Item: Clone,
+    {
+        self.replay.mark(self.position, &self.history)
+    }
+
+    /// Rewind the iterator to `checkpoint`, so that the items consumed since are returned again.
+    ///
+    /// The position and the items returned by [`Lookahead::lookbehind`] are restored as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `checkpoint` was created by a different [`Lookahead`], if the iterator has
+    /// already been reset to an earlier checkpoint, or if the buffer cannot hold the rewound
+    /// items.
+    pub fn reset(&mut self, checkpoint: Checkpoint) {
+        let position = checkpoint.position();
+        let items = self.replay.rewind(checkpoint, &mut self.history);
+        self.position = position;
+        for item in items.into_iter().rev() {
+            self.put_back(item);
+        }
+    }
+
+    /// Release `checkpoint`, keeping everything consumed since it was created.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `checkpoint` was created by a different [`Lookahead`].
+    pub fn commit(&mut self, checkpoint: Checkpoint) {
+        self.replay.commit(checkpoint);
+    }
+
+    /// Run `func`, rewinding any items it consumed if it returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    /// use std::str::Chars;
+    ///
+    /// fn negative(iter: &mut Lookahead<Chars>) -> Option<i64> {
+    ///     iter.next_if_eq(&'-')?;
+    ///     let digit = iter.next()?.to_digit(10)?;
+    ///     Some(-i64::from(digit))
+    /// }
+    ///
+    /// let mut iter = Lookahead::new("-x".chars());
+    ///
+    /// assert_eq!(iter.speculate(negative), None);
+    /// assert_eq!(iter.next(), Some('-'));
+    /// ```
+    pub fn speculate<R, F>(&mut self, func: F) -> Option<R>
+    where
+        F: FnOnce(&mut Self) -> Option<R>,
+        I::Item: Clone,
+    {
+        let checkpoint = self.mark();
+        let result = func(self);
+        match result {
+            Some(_) => self.commit(checkpoint),
+            None => self.reset(checkpoint),
+        }
+        result
+    }
+
+    /// Borrow this iterator as a new [`Lookahead`] that shares its buffer and state.
+    ///
+    /// Unlike wrapping `&mut self` in another [`Lookahead`], items looked at through the
+    /// returned iterator stay buffered here once it is dropped, so it can be handed to a
+    /// sub-parser without losing anything. Items it consumes are consumed from this iterator:
+    /// they advance its position, and are recorded by its history and live checkpoints.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::{Lookahead, LookaheadBuffer};
+    ///
+    /// fn digit<I, B>(iter: &mut Lookahead<I, B>) -> Option<char>
+    /// where
+    ///     I: Iterator<Item = char>,
+    ///     B: LookaheadBuffer<char>,
+    /// {
+    ///     iter.next_if(char::is_ascii_digit)
+    /// }
+    ///
+    /// let mut iter = Lookahead::new("12a".chars());
+    ///
+    /// {
+    ///     let mut inner = iter.reborrow();
+    ///     assert_eq!(digit(&mut *inner), Some('1'));
+    ///     assert_eq!(inner.lookahead(1), Some(&'a'));
+    /// }
+    ///
+    /// assert_eq!(iter.position(), 1);
+    /// assert_eq!(iter.collect::<String>(), "2a");
+    /// ```
+    pub fn reborrow(&mut self) -> Reborrow<'_, I, B> {
+        let unbuffered = Unbuffered {
+            iter: &mut self.iter,
+        };
+        let mut reborrow = Reborrow {
+            inner: Lookahead::with_buffer(unbuffered, &mut self.queue),
+            back: &mut self.back,
+            history: &mut self.history,
+            replay: &mut self.replay,
+            limits: &mut self.limits,
+            instrument: &mut self.instrument,
+            position: &mut self.position,
+        };
+        reborrow.swap();
+        reborrow
+    }
+
+    /// Buffer the items in `range`, returning its bounds clamped to the buffered items.
+    fn buffer<R>(&mut self, range: R) -> (usize, usize)
+    where
+        R: RangeBounds<usize>,
+    {
+        let (start, end) = bounds(range);
+        if end > 0 {
+            self.lookahead(end - 1);
+        }
+        let end = end.min(self.queue.len());
+        (start.min(end), end)
+    }
+
+    /// Buffer the next `n` items for an edit, regardless of the configured [`Limits`].
+    ///
+    /// Panics if the buffer is full before all of them, or the iterator runs out, are buffered.
+    fn buffer_for_edit(&mut self, n: usize) {
+        if n > 0 {
+            self.fill(n - 1);
+        }
+        if self.queue.len() < n && self.queue.is_full() {
+            buffer_full();
+        }
+    }
+
+    /// Create an error for the next item, which was not `description`.
+    #[track_caller]
+    fn unexpected(&mut self, description: &str) -> ExpectError<I::Item>
+    where
+        I::Item: Clone + fmt::Debug,
+    {
+        let found = self.lookahead(0).cloned();
+        let snippet = self.snippet();
+        ExpectError::new(
+            found,
+            String::from(description),
+            self.position,
+            snippet,
+            Location::caller(),
+        )
+    }
+
+    /// Render the items around the next one on a line, and underline the next one below it.
+    fn snippet(&self) -> String
+    where
+        I::Item: fmt::Debug,
+    {
+        let mut line = String::new();
+        let behind = (0..SNIPPET_CONTEXT)
+            .rev()
+            .filter_map(|n| self.history.get(n));
+        for item in behind {
+            line.push_str(&format!("{:?} ", item));
+        }
+        let column = line.chars().count();
+        let next = match self.queue.get(0) {
+            Some(item) => format!("{:?}", item),
+            None => String::from("<end>"),
+        };
+        line.push_str(&next);
+        let ahead = (1..=SNIPPET_CONTEXT).filter_map(|n| self.queue.get(n));
+        for item in ahead {
+            line.push_str(&format!(" {:?}", item));
+        }
+        let padding = " ".repeat(column);
+        let underline = "^".repeat(next.chars().count());
+        format!("{}\n{}{}", line, padding, underline)
+    }
+
+    /// Buffer items up to and including the one `n` iterations ahead, regardless of the
+    /// configured [`Limits`].
+    ///
+    /// Used by methods that consume the items they buffer.
+    fn fill(&mut self, n: usize) {
+        while self.queue.len() <= n && !self.queue.is_full() {
+            match self.pull().or_else(|| self.back.pop_back()) {
+                Some(item) => {
+                    self.queue.push_back(item).unwrap_or_else(|_| buffer_full());
+                    self.observe_buffer();
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Remove the next item without recording it.
+    fn pop(&mut self) -> Option<I::Item> {
+        self.queue
+            .pop_front()
+            .or_else(|| self.pull())
+            .or_else(|| self.back.pop_back())
+    }
+
+    /// Take the next item from the underlying iterator.
+    fn pull(&mut self) -> Option<I::Item> {
+        let item = self.iter.next()?;
+        self.instrument.pulled(&item);
+        Some(item)
+    }
+
+    /// Note the length of the buffer after it has grown.
+    fn observe_buffer(&mut self) {
+        let (len, capacity) = (self.queue.len(), self.queue.capacity());
+        self.instrument.buffered(len, capacity);
+    }
+
+    /// Record `item` as consumed.
+    fn record(&mut self, item: &I::Item) {
+        self.history.record(item);
+        self.replay.record(item);
+        self.position += 1;
+    }
+}
+
+/// The number of items shown on either side of an unexpected item.
+const SNIPPET_CONTEXT: usize = 3;
+
+#[cold]
+fn buffer_full() -> ! {
+    panic!("lookahead buffer is full")
+}
+
+/// Return the start and end of `range`, with an unbounded end as `usize::MAX`.
+fn bounds<R>(range: R) -> (usize, usize)
+where
+    R: RangeBounds<usize>,
+{
+    let start = match range.start_bound() {
+        Bound::Included(&n) => n,
+        Bound::Excluded(&n) => n + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&n) => n.saturating_add(1),
+        Bound::Excluded(&n) => n,
+        Bound::Unbounded => usize::MAX,
+    };
+    (start, end)
+}
+
+impl<I, B> Lookahead<I, B>
+where
+    I: DoubleEndedIterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    /// Return a reference to the item `n` iterations from the back without advancing the
+    /// iterator.
+    ///
+    /// When `n` is `0`, this is the item that would otherwise have been returned from
+    /// `.next_back()`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::Lookahead;
+    ///
+    /// let mut iter = Lookahead::new(1..=3);
+    ///
+    /// assert_eq!(iter.lookback(0), Some(&3));
+    /// assert_eq!(iter.lookback(2), Some(&1));
+    /// assert_eq!(iter.lookback(3), None);
+    /// assert_eq!(iter.next_back(), Some(3));
+    /// ```
+    ///
+    /// Like [`Lookahead::lookahead`], `None` is returned for items beyond the configured
+    /// [`Limits`].
+    pub fn lookback(&mut self, n: usize) -> Option<&I::Item> {
+        let enqueued = self.back.len();
+        if n >= enqueued {
+            let buffered = self.queue.len() + enqueued;
+            self.limits.check_pull(buffered, n - enqueued + 1).ok()?;
+            let instrument = &mut self.instrument;
+            let iter = self.iter.by_ref().rev();
+            let items = iter.take(n - enqueued + 1);
+            self.back
+                .extend(items.inspect(|item| instrument.pulled(item)));
+            while self.back.len() <= n {
+                match self.queue.pop_back() {
+                    Some(item) => self.back.push_back(item),
+                    None => break,
+                }
+            }
+        }
+        self.back.get(n)
+    }
+}
+
+impl<I, B> Iterator for Lookahead<I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.pop()?;
+        self.record(&item);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let queued = self.queue.len() + self.back.len();
+        let (lower, upper) = self.iter.size_hint();
+        (lower + queued, upper.map(|n| n + queued))
+    }
+}
+
+impl<I, B> DoubleEndedIterator for Lookahead<I, B>
+where
+    I: DoubleEndedIterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.back
+            .pop_front()
+            .or_else(|| {
+                let item = self.iter.next_back()?;
+                self.instrument.pulled(&item);
+                Some(item)
+            })
+            .or_else(|| self.queue.pop_back())
+    }
+}
+
+impl<I, B> ExactSizeIterator for Lookahead<I, B>
+where
+    I: ExactSizeIterator,
+    B: LookaheadBuffer<I::Item>,
+{
+}
+
+impl<I, B> FusedIterator for Lookahead<I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+}
+
+/// A draining iterator over the next items of a [`Lookahead`].
+///
+/// This struct is created by [`Lookahead::next_n`].
+pub struct Drain<'a, I, B = DefaultBuffer<<I as Iterator>::Item>>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    lookahead: &'a mut Lookahead<I, B>,
+    remaining: usize,
+}
+
+impl<'a, I, B> Iterator for Drain<'a, I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.lookahead.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, I, B> fmt::Debug for Drain<'a, I, B>
+where
+    I: Iterator + fmt::Debug,
+    I::Item: fmt::Debug,
+    B: LookaheadBuffer<I::Item> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Drain")
+            .field("lookahead", &self.lookahead)
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+impl<'a, I, B> ExactSizeIterator for Drain<'a, I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+}
+
+impl<'a, I, B> FusedIterator for Drain<'a, I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+}
+
+impl<'a, I, B> Drop for Drain<'a, I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+/// An iterator over the remaining items of a [`Lookahead`] together with their positions.
+///
+/// This struct is created by [`Lookahead::spanned`].
+pub struct Spans<'a, I, B = DefaultBuffer<<I as Iterator>::Item>>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    lookahead: &'a mut Lookahead<I, B>,
+}
+
+impl<'a, I, B> Iterator for Spans<'a, I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+    type Item = Spanned<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lookahead.next_spanned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.lookahead.size_hint()
+    }
+}
+
+impl<'a, I, B> fmt::Debug for Spans<'a, I, B>
+where
+    I: Iterator + fmt::Debug,
+    I::Item: fmt::Debug,
+    B: LookaheadBuffer<I::Item> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spans")
+            .field("lookahead", &self.lookahead)
+            .finish()
+    }
+}
+
+impl<'a, I, B> ExactSizeIterator for Spans<'a, I, B>
+where
+    I: ExactSizeIterator,
+    B: LookaheadBuffer<I::Item>,
+{
+}
+
+impl<'a, I, B> FusedIterator for Spans<'a, I, B>
+where
+    I: Iterator,
+    B: LookaheadBuffer<I::Item>,
+{
+}
+
+/// A [`Lookahead`] borrowed from another, sharing its buffer and state.
+///
+/// This struct is created by [`Lookahead::reborrow`]. It dereferences to a [`Lookahead`] over
+/// the items of the borrowed one that have not been buffered yet, whose buffer is the buffer
+/// of the borrowed one. The history, checkpoints, limits and position of the borrowed
+/// [`Lookahead`] are moved into it, and moved back when it is dropped.
+///
+/// Use `&mut *reborrow` to pass it on as a `&mut Lookahead`.
+pub struct Reborrow<'a, I, B>
+where
+    I: Iterator,
+{
+    inner: Lookahead<Unbuffered<'a, I>, &'a mut B>,
+    back: &'a mut VecDeque<I::Item>,
+    history: &'a mut History<I::Item>,
+    replay: &'a mut Replay<I::Item>,
+    limits: &'a mut Limits<I::Item>,
+    instrument: &'a mut Instrument<I::Item>,
+    position: &'a mut usize,
+}
+
+impl<'a, I, B> Reborrow<'a, I, B>
+where
+    I: Iterator,
+{
+    /// Exchange the state of the inner iterator with that of the borrowed one.
+    fn swap(&mut self) {
+        mem::swap(&mut self.inner.back, self.back);
+        mem::swap(&mut self.inner.history, self.history);
+        mem::swap(&mut self.inner.replay, self.replay);
+        mem::swap(&mut self.inner.limits, self.limits);
+        mem::swap(&mut self.inner.instrument, self.instrument);
+        mem::swap(&mut self.inner.position, self.position);
+    }
+}
+
+impl<'a, I, B> Deref for Reborrow<'a, I, B>
+where
+    I: Iterator,
+{
+    type Target = Lookahead<Unbuffered<'a, I>, &'a mut B>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<'a, I, B> DerefMut for Reborrow<'a, I, B>
+where
+    I: Iterator,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<'a, I, B> fmt::Debug for Reborrow<'a, I, B>
+where
+    I: Iterator,
+    Lookahead<Unbuffered<'a, I>, &'a mut B>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reborrow")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<'a, I, B> Drop for Reborrow<'a, I, B>
+where
+    I: Iterator,
+{
+    fn drop(&mut self) {
+        self.swap();
+    }
+}
+
+/// An iterator over the items of a [`Lookahead`] that have not been buffered.
+///
+/// This struct is used by [`Reborrow`].
+#[derive(Debug)]
+pub struct Unbuffered<'a, I: Iterator> {
+    iter: &'a mut Fuse<I>,
+}
+
+impl<'a, I> Iterator for Unbuffered<'a, I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, I> DoubleEndedIterator for Unbuffered<'a, I>
+where
+    I: DoubleEndedIterator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, I> ExactSizeIterator for Unbuffered<'a, I> where I: ExactSizeIterator {}
+
+impl<'a, I> FusedIterator for Unbuffered<'a, I> where I: Iterator {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::RingBuffer;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use core::mem::MaybeUninit;
+
+    #[test]
+    fn zero() {
+        let inner = [1, 2].iter();
+        let mut iter = Lookahead::new(inner);
+        assert_eq!(iter.lookahead(0), Some(&&1));
+    }
+
+    #[test]
+    fn one() {
+        let inner = [1, 2].iter();
+        let mut iter = Lookahead::new(inner);
+        assert_eq!(iter.lookahead(1), Some(&&2));
+    }
+
+    #[test]
+    fn two() {
+        let inner = [1, 2].iter();
+        let mut iter = Lookahead::new(inner);
+        assert_eq!(iter.lookahead(2), None);
+    }
+
+    #[test]
+    fn next() {
+        let inner = [1, 2].iter();
+        let mut iter = Lookahead::new(inner);
+        let _ = iter.next();
+        assert_eq!(iter.lookahead(0), Some(&&2));
+    }
+
+    #[test]
+    fn size_hint() {
+        let inner = [1, 2].iter();
+        let mut iter = Lookahead::new(inner);
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        let _ = iter.lookahead(1);
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        let _ = iter.next();
+        assert_eq!(iter.size_hint(), (1, Some(1)));
+    }
+
+    #[test]
+    fn fused() {
+        fn fused<I: FusedIterator>(iter: I) -> I {
+            iter
+        }
+        let mut iter = fused(Lookahead::new(vec![1].into_iter().chain(None)));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn lookahead_mut() {
+        let mut iter = Lookahead::new(vec![1, 2]);
+        *iter.lookahead_mut(1).unwrap() = 3;
+        assert_eq!(iter.lookahead(1), Some(&3));
+        assert_eq!(iter.lookahead_mut(2), None);
+    }
+
+    #[test]
+    fn next_if() {
+        let mut iter = Lookahead::new(vec![1, 2]);
+        assert_eq!(iter.next_if(|&x| x == 2), None);
+        assert_eq!(iter.next_if_eq(&1), Some(1));
+        assert_eq!(iter.next_if_eq(&2), Some(2));
+        assert_eq!(iter.next_if(|_| true), None);
+    }
+
+    #[test]
+    fn next_if_map() {
+        let mut iter = Lookahead::new(vec![1, 2]);
+        let _ = iter.lookahead(1);
+        assert_eq!(
+            iter.next_if_map(|x| if x == 2 { Ok(x) } else { Err(x) }),
+            None
+        );
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(iter.next_if_map(|x| Ok::<_, i32>(x * 10)), Some(10));
+        assert_eq!(iter.peek(), Some(&2));
+    }
+
+    #[test]
+    fn peek_slice() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4]);
+        let _ = iter.lookahead(1);
+        assert_eq!(iter.peek_slice(1..=2), &[2, 3]);
+        assert_eq!(iter.peek_slice(..0), &[] as &[i32]);
+        assert_eq!(iter.peek_slice(5..), &[] as &[i32]);
+        let _ = iter.next();
+        assert_eq!(iter.peek_slice(..), &[2, 3, 4]);
+    }
+
+    #[test]
+    fn get_many() {
+        let mut iter = Lookahead::new(vec![1, 2]);
+        assert_eq!(iter.get_many([1, 1, 2]), [Some(&2), Some(&2), None]);
+        assert_eq!(iter.get_many([]), []);
+    }
+
+    #[test]
+    fn next_array() {
+        let mut iter = Lookahead::new(vec![1, 2, 3]);
+        assert_eq!(iter.next_array(), Some([]));
+        assert_eq!(iter.next_array(), Some([1, 2]));
+        assert_eq!(iter.next_array::<2>(), None);
+        assert_eq!(iter.next(), Some(3));
+    }
+
+    #[test]
+    fn next_n() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4]);
+        let mut drain = iter.next_n(3);
+        assert_eq!(drain.next(), Some(1));
+        assert_eq!(drain.len(), 2);
+        drop(drain);
+        assert_eq!(iter.next(), Some(4));
+        assert_eq!(iter.next_n(1).next(), None);
+    }
+
+    #[test]
+    fn advance_by() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4]);
+        let _ = iter.lookahead(1);
+        assert_eq!(iter.advance_by(0), Ok(()));
+        assert_eq!(iter.advance_by(3), Ok(()));
+        assert_eq!(iter.size_hint(), (1, Some(1)));
+        assert_eq!(iter.advance_by(2), Err(NonZeroUsize::new(1).unwrap()));
+        assert_eq!(iter.position(), 4);
+    }
+
+    #[test]
+    fn advance_by_records() {
+        let mut iter = Lookahead::with_history(vec![1, 2, 3, 4], 2);
+        let _ = iter.lookahead(0);
+        assert_eq!(iter.advance_by(3), Ok(()));
+        assert_eq!(iter.lookbehind(0), Some(&3));
+        let checkpoint = iter.mark();
+        assert_eq!(iter.advance_by(2), Err(NonZeroUsize::new(1).unwrap()));
+        iter.reset(checkpoint);
+        assert_eq!(iter.next(), Some(4));
+    }
+
+    #[test]
+    fn lookbehind() {
+        let mut iter = Lookahead::with_history(vec![1, 2, 3, 4, 5], 2);
+        let _ = iter.next();
+        assert_eq!(iter.lookbehind(0), Some(&1));
+        assert_eq!(iter.lookbehind(1), None);
+        let _ = iter.next_if_map(Err::<(), _>);
+        assert_eq!(iter.lookbehind(0), Some(&1));
+        let _ = iter.next_n(2);
+        assert_eq!(iter.lookbehind(0), Some(&3));
+        assert_eq!(iter.lookbehind(1), Some(&2));
+        let _ = iter.advance_by(2);
+        assert_eq!(iter.lookbehind(0), Some(&5));
+        assert_eq!(iter.lookbehind(2), None);
+    }
+
+    #[test]
+    fn next_back() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4]);
+        assert_eq!(iter.lookback(0), Some(&4));
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.lookahead(0), Some(&1));
+        assert_eq!(iter.next_back(), Some(3));
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(iter.next_back(), Some(2));
+        assert_eq!(iter.next_back(), Some(1));
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn lookback_meets_lookahead() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4]);
+        assert_eq!(iter.lookahead(1), Some(&2));
+        assert_eq!(iter.lookback(1), Some(&3));
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        assert_eq!(iter.lookahead(3), Some(&4));
+        assert_eq!(iter.lookback(3), Some(&1));
+        assert_eq!(iter.lookahead(2), Some(&3));
+        assert_eq!(iter.lookback(4), None);
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.lookback(2), Some(&2));
+        assert_eq!(iter.peek_slice(..), &[2, 3, 4]);
+        assert_eq!(iter.rev().collect::<Vec<_>>(), vec![4, 3, 2]);
+    }
+
+    #[test]
+    fn put_back() {
+        let mut iter = Lookahead::new(vec![2]);
+        iter.put_back(1);
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(iter.lookahead(1), Some(&2));
+    }
+
+    #[test]
+    fn insert() {
+        let mut iter = Lookahead::new(vec![1, 2]);
+        iter.insert(0, 0);
+        iter.insert(3, 3);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn insert_out_of_bounds() {
+        let mut iter = Lookahead::new(vec![1]);
+        iter.insert(2, 0);
+    }
+
+    #[test]
+    fn remove() {
+        let mut iter = Lookahead::new(vec![1, 2, 3]);
+        assert_eq!(iter.remove(2), Some(3));
+        assert_eq!(iter.remove(2), None);
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+    }
+
+    #[test]
+    fn splice() {
+        let mut iter = Lookahead::new(vec![1, 2, 3]);
+        assert_eq!(iter.splice(..0, vec![0]), vec![]);
+        assert_eq!(iter.splice(2.., vec![9]), vec![2, 3]);
+        assert_eq!(iter.splice(5..9, vec![]), vec![]);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![0, 1, 9]);
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut iter = Lookahead::with_history(vec![1, 2, 3, 4], 4);
+        let _ = iter.next();
+        let outer = iter.mark();
+        let _ = iter.next();
+        let inner = iter.mark();
+        let _ = iter.next_if_map(Ok::<_, i32>);
+        iter.reset(inner);
+        assert_eq!(iter.lookbehind(0), Some(&2));
+        assert_eq!(iter.next_n(2).collect::<Vec<_>>(), vec![3, 4]);
+        iter.reset(outer);
+        assert_eq!(iter.lookbehind(0), Some(&1));
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+    }
+
+    #[test]
+    fn checkpoint_restores_history() {
+        let mut iter = Lookahead::with_history(1..=5, 2);
+        let _ = iter.next();
+        let _ = iter.next();
+        let outer = iter.mark();
+        let _ = iter.next();
+        let inner = iter.mark();
+        let _ = iter.next();
+        let _ = iter.next();
+        assert_eq!(iter.lookbehind(1), Some(&4));
+        iter.reset(inner);
+        assert_eq!(iter.lookbehind(0), Some(&3));
+        assert_eq!(iter.lookbehind(1), Some(&2));
+        let _ = iter.next();
+        iter.reset(outer);
+        assert_eq!(iter.lookbehind(0), Some(&2));
+        assert_eq!(iter.lookbehind(1), Some(&1));
+        assert_eq!(iter.collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn commit() {
+        let mut iter = Lookahead::new(vec![1, 2, 3]);
+        let outer = iter.mark();
+        let _ = iter.next();
+        let inner = iter.mark();
+        let _ = iter.next();
+        iter.commit(inner);
+        iter.reset(outer);
+        assert_eq!(iter.next(), Some(1));
+        let checkpoint = iter.mark();
+        iter.commit(checkpoint);
+        assert_eq!(iter.next(), Some(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "different Lookahead")]
+    fn foreign_checkpoint() {
+        let mut a = Lookahead::new(vec![1]);
+        let mut b = a.clone();
+        let checkpoint = a.mark();
+        b.reset(checkpoint);
+    }
+
+    #[test]
+    #[should_panic(expected = "different Lookahead")]
+    fn stale_checkpoint() {
+        let mut a = Lookahead::new(0..5);
+        let stale = a.mark();
+        drop(a);
+        let mut b = Lookahead::new(0..5);
+        let _checkpoint = b.mark();
+        b.reset(stale);
+    }
+
+    #[test]
+    fn speculate() {
+        let mut iter = Lookahead::new(vec![1, 2, 3]);
+        assert_eq!(iter.speculate(|iter| iter.next_if_eq(&1)), Some(1));
+        assert_eq!(iter.speculate(|iter| iter.nth(1)), Some(3));
+        let mut iter = Lookahead::new(vec![1, 2, 3]);
+        assert_eq!(iter.speculate(|iter| iter.nth(3)), None);
+        assert_eq!(iter.next(), Some(1));
+    }
+
+    #[test]
+    fn lookbehind_disabled() {
+        let mut iter = Lookahead::new(vec![1]);
+        let _ = iter.next();
+        assert_eq!(iter.lookbehind(0), None);
+    }
+
+    #[test]
+    fn small_buffer() {
+        let mut iter = Lookahead::with_buffer(vec![1, 2, 3, 4], SmallBuffer::<_, 2>::new());
+        assert_eq!(iter.lookahead(1), Some(&2));
+        assert!(!iter.queue.spilled());
+        assert_eq!(iter.peek_slice(..), &[1, 2, 3, 4]);
+        assert!(iter.queue.spilled());
+        assert_eq!(iter.splice(1..3, vec![0]), vec![2, 3]);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![1, 0, 4]);
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let mut slots = [MaybeUninit::uninit(); 2];
+        let mut iter = Lookahead::with_buffer(vec![1, 2, 3], RingBuffer::new(&mut slots));
+        assert_eq!(iter.lookahead(2), None);
+        assert_eq!(iter.peek_slice(..), &[1, 2]);
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.next_array(), Some([1, 2]));
+        assert_eq!(iter.lookahead(0), Some(&3));
+        assert_eq!(iter.next_if_map(Err::<(), _>), None);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn ring_buffer_history() {
+        let mut slots = [MaybeUninit::uninit(); 2];
+        let mut iter = Lookahead::with_buffer("(1]".chars(), RingBuffer::new(&mut slots));
+        iter.set_history(2);
+        let _ = iter.next_n(2);
+        let error = iter.expect_eq(&')', "`)`").unwrap_err();
+        assert_eq!(error.snippet(), "'(' '1' ']'\n        ^^^");
+        iter.set_history(1);
+        assert_eq!(iter.lookbehind(0), Some(&'1'));
+        assert_eq!(iter.lookbehind(1), None);
+    }
+
+    #[test]
+    fn default_buffer() {
+        let mut iter = Lookahead::new(1..=3);
+        assert_eq!(iter.lookahead(1), Some(&2));
+        assert!(!iter.queue.spilled());
+        assert_eq!(iter.lookahead(2), Some(&3));
+        assert!(iter.queue.spilled());
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer is full")]
+    fn ring_buffer_overflow() {
+        let mut slots = [MaybeUninit::uninit(); 1];
+        let mut iter = Lookahead::with_buffer(vec![1], RingBuffer::new(&mut slots));
+        let _ = iter.lookahead(0);
+        iter.put_back(0);
+    }
+
+    #[test]
+    fn reborrow() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4, 5]);
+        assert_eq!(iter.next_back(), Some(5));
+        assert_eq!(iter.lookback(0), Some(&4));
+        let _ = iter.lookahead(0);
+        {
+            let mut inner = iter.reborrow();
+            assert_eq!(inner.size_hint(), (4, Some(4)));
+            assert_eq!(inner.next(), Some(1));
+            assert_eq!(inner.lookahead(2), Some(&4));
+            assert_eq!(inner.lookahead(3), None);
+        }
+        assert_eq!(iter.queue.len(), 3);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn reborrow_state() {
+        let mut iter = Lookahead::with_history(vec![1, 2, 3, 4], 2);
+        let checkpoint = iter.mark();
+        {
+            let mut inner = iter.reborrow();
+            assert_eq!(inner.next(), Some(1));
+            assert_eq!(inner.next(), Some(2));
+            assert_eq!(inner.position(), 2);
+            assert_eq!(inner.lookbehind(1), Some(&1));
+        }
+        assert_eq!(iter.position(), 2);
+        assert_eq!(iter.lookbehind(0), Some(&2));
+        iter.reset(checkpoint);
+        assert_eq!(iter.position(), 0);
+        let inner_checkpoint = iter.reborrow().mark();
+        assert_eq!(iter.next_n(3).collect::<Vec<_>>(), vec![1, 2, 3]);
+        iter.reset(inner_checkpoint);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn position() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4]);
+        assert_eq!(iter.position_of(2), 2);
+        let checkpoint = iter.mark();
+        assert_eq!(iter.next_spanned(), Some(Spanned::new(1, 0, 1)));
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.next_if_eq(&2), Some(2));
+        assert_eq!(iter.position(), 2);
+        iter.reset(checkpoint);
+        assert_eq!(iter.position(), 0);
+        assert_eq!(iter.advance_by(5), Err(NonZeroUsize::new(2).unwrap()));
+        assert_eq!(iter.position_of(1), 4);
+    }
+
+    #[test]
+    fn spanned() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4]);
+        {
+            let mut inner = iter.reborrow();
+            let spans = inner.spanned().take(2).collect::<Vec<_>>();
+            assert_eq!(spans, vec![Spanned::new(1, 0, 1), Spanned::new(2, 1, 2)]);
+        }
+        assert_eq!(iter.spanned().len(), 2);
+        let spans = iter.spanned().collect::<Vec<_>>();
+        assert_eq!(spans, vec![Spanned::new(3, 2, 3), Spanned::new(4, 3, 4)]);
+        assert_eq!(iter.spanned().next(), None);
+    }
+
+    #[test]
+    fn longest_match() {
+        let set = vec![vec![1], vec![1, 2, 3], vec![]]
+            .into_iter()
+            .collect::<TrieSet<_>>();
+        let mut iter = Lookahead::new(vec![1, 2, 4]);
+        assert_eq!(iter.longest_match(&set), Some(TrieMatch::new(0, 1)));
+        assert_eq!(iter.queue.len(), 3);
+        iter.next();
+        assert_eq!(iter.longest_match(&set), Some(TrieMatch::new(2, 0)));
+        assert_eq!(iter.longest_match(&TrieSet::new()), None);
+    }
+
+    #[test]
+    fn matches_ahead() {
+        let pattern = Pattern::seq(vec![
+            Pattern::eq(1),
+            Pattern::any().repeat(..2),
+            Pattern::eq(3),
+        ]);
+        let mut iter = Lookahead::new(vec![1, 3, 1, 2, 3, 4, 5]);
+        assert_eq!(iter.matches_ahead(&pattern), Some(2));
+        assert_eq!(iter.queue.len(), 3);
+        iter.next();
+        assert_eq!(iter.matches_ahead(&pattern), None);
+        iter.next();
+        assert_eq!(iter.matches_ahead(&pattern), Some(3));
+        assert_eq!(iter.matches_ahead(&Pattern::any().star()), Some(5));
+    }
+
+    #[test]
+    fn expect_seq() {
+        let mut iter = Lookahead::new(vec![1, 2, 3]);
+        assert_eq!(iter.expect_seq(&[1, 3]), None);
+        assert_eq!(iter.expect_seq(&[1, 2]), Some(vec![1, 2]));
+        assert_eq!(iter.expect_seq(&[3, 4]), None);
+        assert!(iter.starts_with(&[]));
+        assert_eq!(iter.next(), Some(3));
+    }
+
+    #[test]
+    fn expect() {
+        let mut iter = Lookahead::new(vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(iter.expect_eq(&1, "one"), Ok(1));
+        let line = line!() + 1;
+        let error = iter.expect(|&x| x > 2, "more than two").unwrap_err();
+        assert_eq!(error.caller().line(), line);
+        assert_eq!(error.position(), 1);
+        assert_eq!(error.snippet(), "2\n^");
+        let _ = iter.lookahead(4);
+        let error = iter.expect_end().unwrap_err();
+        assert_eq!(error.snippet(), "2 3 4 5\n^");
+        iter.advance_by(5).unwrap();
+        assert_eq!(iter.expect_end(), Ok(()));
+        let error = iter.expect_eq(&7, "seven").unwrap_err();
+        assert_eq!(error.found(), None);
+        assert_eq!(
+            error.to_string(),
+            "expected seven, found end of input at position 6"
+        );
+    }
+
+    #[test]
+    fn expect_with_history() {
+        let mut iter = Lookahead::with_history(vec!["let", "x", "=", "=", "1"], 8);
+        iter.advance_by(3).unwrap();
+        let _ = iter.lookahead(1);
+        let error = iter.expect_eq(&"1", "an expression").unwrap_err();
+        assert_eq!(
+            error.snippet(),
+            "\"let\" \"x\" \"=\" \"=\" \"1\"\n              ^^^"
+        );
+        let error = error.merge(iter.expect_eq(&"(", "`(`").unwrap_err());
+        assert_eq!(error.expected(), &["an expression", "`(`"]);
+    }
+
+    #[test]
+    fn max_items() {
+        let mut iter = Lookahead::with_limits(1..=5, Limits::new().max_items(2));
+        assert_eq!(iter.try_lookahead(2), Err(LimitExceeded::Items(2)));
+        assert_eq!(iter.queue.len(), 2);
+        assert_eq!(iter.lookahead(1), Some(&2));
+        assert_eq!(iter.lookahead(2), None);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.try_lookahead(1), Ok(Some(&3)));
+        assert_eq!(iter.peek_slice(..), &[2, 3]);
+    }
+
+    #[test]
+    fn limits_do_not_apply_to_consuming() {
+        let limits = Limits::new().max_items(2);
+        let mut iter = Lookahead::with_limits(1..=10, limits);
+        assert_eq!(iter.next_n(5).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(iter.next_array(), Some([6, 7, 8]));
+        assert_eq!(iter.position(), 8);
+    }
+
+    #[test]
+    fn limited_views() {
+        let mut iter = Lookahead::with_limits(1..=6, Limits::new().max_items(4));
+        assert_eq!(iter.peek_slice(..), &[1, 2, 3, 4]);
+        assert_eq!(iter.peek_slice(2..6), &[3, 4]);
+        assert_eq!(iter.get_many([0, 5, 3]), [Some(&1), None, Some(&4)]);
+    }
+
+    #[test]
+    fn limits_do_not_apply_to_edits() {
+        let limits = Limits::new().max_items(2);
+        let mut iter = Lookahead::with_limits(1..=6, limits);
+        assert_eq!(iter.splice(3..4, vec![0]), vec![4]);
+        iter.insert(5, 7);
+        assert_eq!(iter.remove(4), Some(5));
+        assert_eq!(iter.remove(6), None);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![1, 2, 3, 0, 7, 6]);
+
+        let mut iter = Lookahead::with_limits(1..=10, limits);
+        iter.insert(3, 0);
+        assert_eq!(iter.splice(8.., None), vec![8, 9, 10]);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![1, 2, 3, 0, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer is full")]
+    fn edit_past_capacity() {
+        let mut slots = [MaybeUninit::uninit(); 2];
+        let mut iter = Lookahead::with_buffer(1..=6, RingBuffer::new(&mut slots));
+        iter.splice(3..4, vec![0]);
+    }
+
+    #[test]
+    fn limited_lookback() {
+        let mut iter = Lookahead::with_limits(1..=5, Limits::new().max_items(2));
+        assert_eq!(iter.lookback(2), None);
+        assert_eq!(iter.lookback(1), Some(&4));
+        assert_eq!(iter.lookahead(0), Some(&1));
+        assert_eq!(iter.lookback(1), Some(&4));
+        assert_eq!(iter.lookback(2), None);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn max_weight() {
+        let words = vec!["a", "bbb", "cc", "d"];
+        let limits = Limits::new().max_weight(4, |s: &&str| s.len());
+        let mut iter = Lookahead::with_limits(words, limits);
+        assert_eq!(iter.try_lookahead(1), Ok(Some(&"bbb")));
+        assert_eq!(iter.try_lookahead(2), Err(LimitExceeded::Weight(4)));
+        assert_eq!(iter.next(), Some("a"));
+        assert_eq!(iter.try_lookahead(1), Ok(Some(&"cc")));
+    }
+
+    #[test]
+    fn scan_budget() {
+        let mut iter = Lookahead::with_limits(1.., Limits::new().scan_budget(3));
+        assert_eq!(iter.try_lookahead(3), Err(LimitExceeded::ScanBudget(3)));
+        assert_eq!(iter.try_lookahead(2), Ok(Some(&3)));
+        assert_eq!(iter.try_lookahead(5), Ok(Some(&6)));
+        assert_eq!(iter.queue.len(), 6);
+    }
+
+    #[test]
+    fn capacity_limit() {
+        let mut slots = [MaybeUninit::uninit(); 2];
+        let mut iter = Lookahead::with_buffer(1..=3, RingBuffer::new(&mut slots));
+        assert_eq!(iter.try_lookahead(2), Err(LimitExceeded::Capacity));
+        assert_eq!(iter.try_lookahead(1), Ok(Some(&2)));
+    }
+
+    #[test]
+    fn consuming_past_capacity() {
+        let mut slots = [MaybeUninit::uninit(); 2];
+        let mut iter = Lookahead::with_buffer(1..=10, RingBuffer::new(&mut slots));
+        iter.set_history(3);
+        assert_eq!(iter.next_n(5).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(iter.next_array(), Some([3, 4, 5]));
+        assert_eq!(iter.position(), 5);
+        assert_eq!(iter.lookbehind(2), Some(&3));
+        assert_eq!(iter.next_back(), Some(10));
+        assert_eq!(iter.next_array::<5>(), None);
+        assert_eq!(iter.lookback(0), Some(&9));
+        assert_eq!(iter.next_array(), Some([6, 7, 8, 9]));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "instrument")]
+    fn metrics() {
+        let mut iter = Lookahead::with_capacity(1..=10, 1);
+        iter.lookahead(3);
+        let _ = iter.next_n(5);
+        let _ = iter.next_back();
+        assert_eq!(iter.metrics().pulls(), 6);
+        assert_eq!(iter.metrics().peak_len(), 5);
+        assert!(iter.metrics().reallocations() > 0);
+        iter.reset_metrics();
+        assert_eq!(iter.metrics().pulls(), 0);
+        assert_eq!(iter.metrics().depths().count(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "instrument")]
+    fn max_depth() {
+        let mut iter = Lookahead::new(1..=10);
+        let line = line!();
+        for n in 0..3 {
+            iter.lookahead(n);
+        }
+        iter.peek();
+        let depth = |line| {
+            let mut depths = iter.metrics().depths();
+            depths
+                .find(|(site, _)| site.line() == line)
+                .map(|(_, depth)| depth)
+        };
+        assert_eq!(depth(line + 2), Some(3));
+        assert_eq!(depth(line + 4), Some(1));
+        let (site, _) = iter.metrics().depths().next().unwrap();
+        assert_eq!(iter.metrics().max_depth(site), Some(3));
+    }
+
+    #[test]
+    #[cfg(all(feature = "instrument", feature = "std"))]
+    fn on_pull() {
+        use std::sync::{Arc, Mutex};
+
+        let pulled = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&pulled);
+        let mut iter = Lookahead::new(1..=4);
+        iter.on_pull(move |&item| log.lock().unwrap().push(item));
+        iter.lookahead(1);
+        let _ = iter.next_back();
+        let _ = iter.next();
+        iter.lookback(0);
+        assert_eq!(*pulled.lock().unwrap(), vec![1, 2, 4, 3]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "instrument", feature = "std"))]
+    fn on_pull_through_reborrow() {
+        use std::sync::{Arc, Mutex};
+
+        let pulled = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&pulled);
+        let mut iter = Lookahead::new(1..=5);
+        iter.on_pull(move |&item| log.lock().unwrap().push(item));
+        iter.lookahead(0);
+        {
+            let mut inner = iter.reborrow();
+            inner.lookahead(2);
+            let _ = inner.next_back();
+            assert_eq!(inner.metrics().pulls(), 4);
+        }
+        assert_eq!(iter.metrics().pulls(), 4);
+        assert_eq!(iter.metrics().peak_len(), 3);
+        assert_eq!(*pulled.lock().unwrap(), vec![1, 2, 3, 5]);
+    }
+}
@@ -0,0 +1,351 @@
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Bound, RangeBounds};
+
+/// A regular expression over items, matched against the upcoming items of a [`Lookahead`].
+///
+/// The atoms of a pattern are predicates over single items. They are combined by sequence,
+/// alternation and repetition.
+///
+/// See [`Lookahead::matches_ahead`].
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::{Lookahead, Pattern};
+///
+/// // A qualified name such as `a::b::c`.
+/// let ident = || Pattern::pred(|s: &&str| s.chars().all(char::is_alphabetic));
+/// let path = Pattern::seq(vec![
+///     ident(),
+///     Pattern::seq(vec![Pattern::eq("::"), ident()]).star(),
+/// ]);
+///
+/// let mut iter = Lookahead::new(vec!["a", "::", "b", "::", "(", ")"]);
+/// assert_eq!(iter.matches_ahead(&path), Some(3));
+/// ```
+///
+/// [`Lookahead`]: crate::Lookahead
+/// [`Lookahead::matches_ahead`]: crate::Lookahead::matches_ahead
+pub struct Pattern<'p, T> {
+    program: Vec<Inst<'p, T>>,
+}
+
+impl<'p, T> Pattern<'p, T> {
+    /// Match a single item that satisfies `pred`.
+    pub fn pred<P>(pred: P) -> Self
+    where
+        P: Fn(&T) -> bool + 'p,
+    {
+        Pattern {
+            program: vec![Inst::Pred(Rc::new(pred))],
+        }
+    }
+
+    /// Match a single item equal to `value`.
+    pub fn eq(value: T) -> Self
+    where
+        T: PartialEq + 'p,
+    {
+        Pattern::pred(move |item| *item == value)
+    }
+
+    /// Match any single item.
+    pub fn any() -> Self {
+        Pattern::pred(|_| true)
+    }
+
+    /// Match each of `patterns` in turn.
+    ///
+    /// An empty sequence matches no items.
+    pub fn seq<P>(patterns: P) -> Self
+    where
+        P: IntoIterator<Item = Pattern<'p, T>>,
+    {
+        let mut program = Vec::new();
+        for pattern in patterns {
+            emit(&mut program, &pattern.program);
+        }
+        Pattern { program }
+    }
+
+    /// Match any one of `patterns`.
+    ///
+    /// An empty alternation never matches.
+    pub fn alt<P>(patterns: P) -> Self
+    where
+        P: IntoIterator<Item = Pattern<'p, T>>,
+    {
+        let mut patterns = patterns.into_iter().peekable();
+        let mut program = Vec::new();
+        let mut jumps = Vec::new();
+        while let Some(pattern) = patterns.next() {
+            if patterns.peek().is_none() {
+                emit(&mut program, &pattern.program);
+                break;
+            }
+            let split = program.len();
+            program.push(Inst::Split(split + 1, 0));
+            emit(&mut program, &pattern.program);
+            jumps.push(program.len());
+            program.push(Inst::Jump(0));
+            let next = program.len();
+            program[split] = Inst::Split(split + 1, next);
+        }
+        if program.is_empty() {
+            program.push(Inst::Fail);
+        }
+        let end = program.len();
+        for jump in jumps {
+            program[jump] = Inst::Jump(end);
+        }
+        Pattern { program }
+    }
+
+    /// Match this pattern zero or one times.
+    pub fn optional(self) -> Self {
+        self.repeat(..=1)
+    }
+
+    /// Match this pattern any number of times.
+    pub fn star(self) -> Self {
+        self.repeat(..)
+    }
+
+    /// Match this pattern one or more times.
+    pub fn plus(self) -> Self {
+        self.repeat(1..)
+    }
+
+    /// Match this pattern a number of times in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is empty.
+    pub fn repeat<R>(self, range: R) -> Self
+    where
+        R: RangeBounds<usize>,
+    {
+        let min = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(&n) => Some(n),
+            Bound::Excluded(&n) => Some(n.checked_sub(1).unwrap_or_else(|| empty_repeat())),
+            Bound::Unbounded => None,
+        };
+        match max {
+            Some(max) if min > max => empty_repeat(),
+            _ => {}
+        }
+        let mut program = Vec::new();
+        for _ in 0..min {
+            emit(&mut program, &self.program);
+        }
+        match max {
+            None => {
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                emit(&mut program, &self.program);
+                program.push(Inst::Jump(split));
+                let end = program.len();
+                program[split] = Inst::Split(split + 1, end);
+            }
+            Some(max) => {
+                let mut splits = Vec::new();
+                for _ in min..max {
+                    splits.push(program.len());
+                    program.push(Inst::Split(program.len() + 1, 0));
+                    emit(&mut program, &self.program);
+                }
+                let end = program.len();
+                for split in splits {
+                    program[split] = Inst::Split(split + 1, end);
+                }
+            }
+        }
+        Pattern { program }
+    }
+}
+
+impl<T> fmt::Debug for Pattern<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pattern")
+            .field("program", &self.program)
+            .finish()
+    }
+}
+
+#[cold]
+fn empty_repeat() -> ! {
+    panic!("repeat range is empty")
+}
+
+/// Append `child` to `program`, moving its jump targets along with it.
+///
+/// A program matches once it runs off its end, so the end of `child` continues into whatever
+/// follows it in `program`.
+fn emit<'p, T>(program: &mut Vec<Inst<'p, T>>, child: &[Inst<'p, T>]) {
+    let base = program.len();
+    program.extend(child.iter().map(|inst| match inst {
+        Inst::Pred(pred) => Inst::Pred(Rc::clone(pred)),
+        Inst::Split(a, b) => Inst::Split(a + base, b + base),
+        Inst::Jump(target) => Inst::Jump(target + base),
+        Inst::Fail => Inst::Fail,
+    }));
+}
+
+/// An instruction of a compiled [`Pattern`].
+///
+/// A program matches when it continues past its last instruction.
+pub(crate) enum Inst<'p, T> {
+    /// Consume an item that satisfies the predicate.
+    Pred(Rc<dyn Fn(&T) -> bool + 'p>),
+    /// Continue at both targets.
+    Split(usize, usize),
+    Jump(usize),
+    Fail,
+}
+
+impl<T> fmt::Debug for Inst<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inst::Pred(_) => f.write_str("Pred"),
+            Inst::Split(a, b) => f.debug_tuple("Split").field(a).field(b).finish(),
+            Inst::Jump(target) => f.debug_tuple("Jump").field(target).finish(),
+            Inst::Fail => f.write_str("Fail"),
+        }
+    }
+}
+
+/// Runs a compiled [`Pattern`] on items one at a time, tracking every way it could match at
+/// once.
+pub(crate) struct Matcher<'a, 'p, T> {
+    program: &'a [Inst<'p, T>],
+    threads: Vec<usize>,
+    next: Vec<usize>,
+    seen: Vec<bool>,
+    steps: usize,
+    longest: Option<usize>,
+}
+
+impl<'a, 'p, T> Matcher<'a, 'p, T> {
+    pub(crate) fn new(pattern: &'a Pattern<'p, T>) -> Self {
+        let program = &pattern.program[..];
+        let mut matcher = Matcher {
+            program,
+            threads: Vec::new(),
+            next: Vec::new(),
+            seen: vec![false; program.len() + 1],
+            steps: 0,
+            longest: None,
+        };
+        matcher.add(0);
+        matcher.threads = core::mem::take(&mut matcher.next);
+        matcher
+    }
+
+    /// Return `true` if more items could extend the match.
+    pub(crate) fn is_alive(&self) -> bool {
+        !self.threads.is_empty()
+    }
+
+    /// Return the length of the longest match so far.
+    pub(crate) fn longest(&self) -> Option<usize> {
+        self.longest
+    }
+
+    /// Advance every thread past `item`.
+    pub(crate) fn step(&mut self, item: &T) {
+        self.steps += 1;
+        for seen in &mut self.seen {
+            *seen = false;
+        }
+        let threads = core::mem::take(&mut self.threads);
+        for &pc in &threads {
+            if let Some(Inst::Pred(pred)) = self.program.get(pc) {
+                if pred(item) {
+                    self.add(pc + 1);
+                }
+            }
+        }
+        self.threads = core::mem::replace(&mut self.next, threads);
+        self.next.clear();
+    }
+
+    /// Add a thread at `pc`, following jumps and splits.
+    fn add(&mut self, pc: usize) {
+        if self.seen[pc] {
+            return;
+        }
+        self.seen[pc] = true;
+        match self.program.get(pc) {
+            Some(Inst::Pred(_)) => self.next.push(pc),
+            Some(&Inst::Split(a, b)) => {
+                self.add(a);
+                self.add(b);
+            }
+            Some(&Inst::Jump(target)) => self.add(target),
+            Some(Inst::Fail) => {}
+            None => self.longest = Some(self.steps),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn longest(pattern: &Pattern<'_, char>, input: &str) -> Option<usize> {
+        let mut matcher = Matcher::new(pattern);
+        for c in input.chars() {
+            if !matcher.is_alive() {
+                break;
+            }
+            matcher.step(&c);
+        }
+        matcher.longest()
+    }
+
+    #[test]
+    fn repetition() {
+        let a = || Pattern::eq('a');
+        assert_eq!(longest(&a().star(), "aab"), Some(2));
+        assert_eq!(longest(&a().star(), "b"), Some(0));
+        assert_eq!(longest(&a().plus(), "b"), None);
+        assert_eq!(longest(&a().optional(), "aa"), Some(1));
+        assert_eq!(longest(&a().repeat(2..=3), "aaaa"), Some(3));
+        assert_eq!(longest(&a().repeat(2..3), "a"), None);
+        assert_eq!(longest(&a().optional().star(), "aa"), Some(2));
+        assert_eq!(longest(&a().repeat(0..=0), "a"), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "repeat range is empty")]
+    fn inverted_repeat() {
+        let _ = Pattern::eq('a').repeat((Bound::Included(3), Bound::Excluded(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "repeat range is empty")]
+    fn empty_repeat() {
+        let _ = Pattern::eq('a').repeat(..0);
+    }
+
+    #[test]
+    fn alternation() {
+        let word = |s: &'static str| Pattern::seq(s.chars().map(Pattern::eq));
+        let pattern = Pattern::alt(vec![word("ab"), word("abcd"), word("x")]);
+        assert_eq!(longest(&pattern, "abcd"), Some(4));
+        assert_eq!(longest(&pattern, "abc"), Some(2));
+        assert_eq!(longest(&pattern, "x"), Some(1));
+        assert_eq!(longest(&Pattern::alt(vec![]), "x"), None);
+        assert_eq!(longest(&Pattern::seq(vec![]), "x"), Some(0));
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// The default number of items the producer thread may run ahead of the consumer.
+const DEFAULT_BOUND: usize = 64;
+
+/// An iterator with arbitrary lookahead whose underlying iterator runs on a background thread.
+///
+/// The background thread eagerly pulls items into a bounded channel, so that an expensive
+/// iterator can make progress while the consumer is busy with earlier items.
+///
+/// If the underlying iterator panics, the panic is resumed on the consumer thread once every
+/// item produced before it has been consumed. Dropping a [`PrefetchLookahead`] stops the
+/// background thread as soon as the item it is producing is finished.
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// use lookahead::PrefetchLookahead;
+///
+/// let mut iter = PrefetchLookahead::new(1..=3);
+///
+/// assert_eq!(iter.lookahead(1), Some(&2));
+/// assert_eq!(iter.next(), Some(1));
+/// assert_eq!(iter.next(), Some(2));
+/// assert_eq!(iter.next(), Some(3));
+/// assert_eq!(iter.next(), None);
+/// ```
+#[derive(Debug)]
+pub struct PrefetchLookahead<T> {
+    receiver: Receiver<T>,
+    queue: VecDeque<T>,
+    producer: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> PrefetchLookahead<T> {
+    /// Create a [`PrefetchLookahead`] iterator over the given iterable.
+    ///
+    /// The background thread runs at most 64 items ahead of the consumer.
+    pub fn new<U>(iterable: U) -> Self
+    where
+        U: IntoIterator<Item = T>,
+        U::IntoIter: Send + 'static,
+    {
+        PrefetchLookahead::with_bound(iterable, DEFAULT_BOUND)
+    }
+
+    /// Create a [`PrefetchLookahead`] iterator over the given iterable, whose background thread
+    /// runs at most `bound` items ahead of the consumer.
+    ///
+    /// Items that have been looked ahead at do not count towards the bound.
+    pub fn with_bound<U>(iterable: U, bound: usize) -> Self
+    where
+        U: IntoIterator<Item = T>,
+        U::IntoIter: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(bound);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut iter = iterable.into_iter();
+        let stopped = Arc::clone(&stop);
+        let producer = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                let item = match iter.next() {
+                    Some(item) => item,
+                    None => break,
+                };
+                if sender.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+        PrefetchLookahead {
+            receiver,
+            queue: VecDeque::new(),
+            producer: Some(producer),
+            stop,
+        }
+    }
+}
+
+impl<T> PrefetchLookahead<T> {
+    /// Return a reference to the item `n` iterations ahead without advancing the iterator.
+    ///
+    /// This blocks until the background thread has produced the item, or has finished.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the underlying iterator panicked before producing the item.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::PrefetchLookahead;
+    ///
+    /// let mut iter = PrefetchLookahead::new(vec!["a", "b"]);
+    ///
+    /// assert_eq!(iter.lookahead(0), Some(&"a"));
+    /// assert_eq!(iter.lookahead(2), None);
+    /// ```
+    pub fn lookahead(&mut self, n: usize) -> Option<&T> {
+        while self.queue.len() <= n {
+            match self.receive() {
+                Some(item) => self.queue.push_back(item),
+                None => break,
+            }
+        }
+        self.queue.get(n)
+    }
+
+    /// Return a reference to the next item without advancing the iterator.
+    ///
+    /// Equivalent to `.lookahead(0)`.
+    pub fn peek(&mut self) -> Option<&T> {
+        self.lookahead(0)
+    }
+
+    /// Wait for the next item from the background thread.
+    ///
+    /// Once the background thread has finished, its panic is resumed, if it panicked.
+    fn receive(&mut self) -> Option<T> {
+        match self.receiver.recv() {
+            Ok(item) => Some(item),
+            Err(_) => {
+                if let Some(Err(payload)) = self.producer.take().map(JoinHandle::join) {
+                    panic::resume_unwind(payload);
+                }
+                None
+            }
+        }
+    }
+}
+
+impl<T> Iterator for PrefetchLookahead<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.queue.pop_front().or_else(|| self.receive())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.producer {
+            Some(_) => (self.queue.len(), None),
+            None => (self.queue.len(), Some(self.queue.len())),
+        }
+    }
+}
+
+impl<T> FusedIterator for PrefetchLookahead<T> {}
+
+impl<T> Drop for PrefetchLookahead<T> {
+    fn drop(&mut self) {
+        // The background thread is not joined, since it may be in the middle of producing an
+        // item. It stops once it sees the flag or fails to send to the closed channel.
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Sender;
+    use std::time::Duration;
+
+    #[test]
+    fn lookahead() {
+        let mut iter = PrefetchLookahead::with_bound(1..=5, 0);
+        assert_eq!(iter.lookahead(3), Some(&4));
+        assert_eq!(iter.size_hint(), (4, None));
+        assert_eq!(iter.by_ref().take(2).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(iter.peek(), Some(&3));
+        assert_eq!(iter.lookahead(3), None);
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "producer failed")]
+    fn propagates_panic() {
+        let inner = (1..).map(|n| if n < 3 { n } else { panic!("producer failed") });
+        let mut iter = PrefetchLookahead::new(inner);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.lookahead(0), Some(&2));
+        iter.lookahead(1);
+    }
+
+    struct Endless(Sender<()>);
+
+    impl Iterator for Endless {
+        type Item = ();
+
+        fn next(&mut self) -> Option<()> {
+            Some(())
+        }
+    }
+
+    impl Drop for Endless {
+        fn drop(&mut self) {
+            let _ = self.0.send(());
+        }
+    }
+
+    #[test]
+    fn drop_stops_producer() {
+        let (sender, dropped) = mpsc::channel();
+        let mut iter = PrefetchLookahead::with_bound(Endless(sender), 4);
+        assert_eq!(iter.lookahead(10), Some(&()));
+        drop(iter);
+        assert_eq!(dropped.recv_timeout(Duration::from_secs(10)), Ok(()));
+    }
+}
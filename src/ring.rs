@@ -0,0 +1,288 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::slice;
+
+/// A double-ended queue stored in a fixed slice of slots.
+pub(crate) struct Ring<T, S>
+where
+    S: AsRef<[MaybeUninit<T>]> + AsMut<[MaybeUninit<T>]>,
+{
+    slots: S,
+    head: usize,
+    len: usize,
+    marker: PhantomData<T>,
+}
+
+/// A double-ended queue of at most `N` items, stored inline.
+pub(crate) type ArrayRing<T, const N: usize> = Ring<T, [MaybeUninit<T>; N]>;
+
+impl<T, const N: usize> ArrayRing<T, N> {
+    pub(crate) fn new() -> Self {
+        // SAFETY: an array of `MaybeUninit` does not require initialization.
+        Ring::from_slots(unsafe { MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init() })
+    }
+
+    /// Return the items as an array in order, or the ring itself if it is not full.
+    pub(crate) fn into_array(mut self) -> Result<[T; N], Self> {
+        if !self.is_full() {
+            return Err(self);
+        }
+        self.make_contiguous();
+        self.len = 0;
+        // SAFETY: every slot was initialized, and is no longer considered part of the ring.
+        // `[MaybeUninit<T>; N]` has the same layout as `[T; N]`.
+        Ok(unsafe { ptr::read(&self.slots as *const [MaybeUninit<T>; N] as *const [T; N]) })
+    }
+}
+
+impl<T, S> Ring<T, S>
+where
+    S: AsRef<[MaybeUninit<T>]> + AsMut<[MaybeUninit<T>]>,
+{
+    /// Create an empty ring over `slots`, whose contents are ignored.
+    pub(crate) fn from_slots(slots: S) -> Self {
+        Ring {
+            slots,
+            head: 0,
+            len: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.as_ref().len()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+
+    /// Return the physical index of the logical index `i`, which must be less than the capacity.
+    fn slot(&self, i: usize) -> usize {
+        (self.head + i) % self.capacity()
+    }
+
+    pub(crate) fn get(&self, i: usize) -> Option<&T> {
+        if i < self.len {
+            let slot = self.slot(i);
+            // SAFETY: the first `len` slots after `head` are initialized.
+            Some(unsafe { &*self.slots.as_ref()[slot].as_ptr() })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        if i < self.len {
+            let slot = self.slot(i);
+            // SAFETY: the first `len` slots after `head` are initialized.
+            Some(unsafe { &mut *self.slots.as_mut()[slot].as_mut_ptr() })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn push_back(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        let slot = self.slot(self.len);
+        self.slots.as_mut()[slot] = MaybeUninit::new(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub(crate) fn push_front(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        self.head = self.slot(self.capacity() - 1);
+        self.slots.as_mut()[self.head] = MaybeUninit::new(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let slot = self.head;
+        self.head = self.slot(1);
+        self.len -= 1;
+        // SAFETY: the slot was initialized and is no longer considered part of the ring.
+        Some(unsafe { ptr::read(self.slots.as_ref()[slot].as_ptr()) })
+    }
+
+    pub(crate) fn pop_back(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let slot = self.slot(self.len);
+        // SAFETY: the slot was initialized and is no longer considered part of the ring.
+        Some(unsafe { ptr::read(self.slots.as_ref()[slot].as_ptr()) })
+    }
+
+    /// Insert `item` at index `i`, shifting the items after it towards the back.
+    ///
+    /// Panics if `i` is greater than the length.
+    pub(crate) fn insert(&mut self, i: usize, item: T) -> Result<(), T> {
+        assert!(i <= self.len, "index out of bounds");
+        self.push_back(item)?;
+        for j in (i..self.len - 1).rev() {
+            self.swap(j, j + 1);
+        }
+        Ok(())
+    }
+
+    /// Remove the item at index `i`, shifting the items after it towards the front.
+    pub(crate) fn remove(&mut self, i: usize) -> Option<T> {
+        if i >= self.len {
+            return None;
+        }
+        for j in i..self.len - 1 {
+            self.swap(j, j + 1);
+        }
+        self.pop_back()
+    }
+
+    /// Rearrange the slots so that the items are stored in order, and return them as a slice.
+    pub(crate) fn make_contiguous(&mut self) -> &mut [T] {
+        let head = self.head;
+        self.slots.as_mut().rotate_left(head);
+        self.head = 0;
+        let items = &mut self.slots.as_mut()[..self.len];
+        // SAFETY: the first `len` slots are initialized, and `MaybeUninit<T>` has the same
+        // layout as `T`.
+        unsafe { slice::from_raw_parts_mut(items.as_mut_ptr() as *mut T, items.len()) }
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        let (i, j) = (self.slot(i), self.slot(j));
+        self.slots.as_mut().swap(i, j);
+    }
+}
+
+impl<T, S> Drop for Ring<T, S>
+where
+    S: AsRef<[MaybeUninit<T>]> + AsMut<[MaybeUninit<T>]>,
+{
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T, const N: usize> Clone for ArrayRing<T, N>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        let mut ring = ArrayRing::new();
+        for i in 0..self.len {
+            let _ = ring.push_back(self.get(i).unwrap().clone());
+        }
+        ring
+    }
+}
+
+impl<T, S> fmt::Debug for Ring<T, S>
+where
+    T: fmt::Debug,
+    S: AsRef<[MaybeUninit<T>]> + AsMut<[MaybeUninit<T>]>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.len).map(|i| self.get(i).unwrap()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn wraps_around() {
+        let mut ring = ArrayRing::<_, 3>::new();
+        assert_eq!(ring.push_back(1), Ok(()));
+        assert_eq!(ring.push_back(2), Ok(()));
+        assert_eq!(ring.pop_front(), Some(1));
+        assert_eq!(ring.push_back(3), Ok(()));
+        assert_eq!(ring.push_back(4), Ok(()));
+        assert_eq!(ring.push_back(5), Err(5));
+        assert_eq!(ring.get(0), Some(&2));
+        assert_eq!(ring.get(2), Some(&4));
+        assert_eq!(ring.pop_back(), Some(4));
+        assert_eq!(ring.pop_back(), Some(3));
+        assert_eq!(ring.pop_front(), Some(2));
+        assert_eq!(ring.pop_front(), None);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut ring = ArrayRing::<_, 0>::new();
+        assert_eq!(ring.push_front(1), Err(1));
+        assert_eq!(ring.get(0), None);
+        assert_eq!(ring.pop_back(), None);
+        assert_eq!(ring.make_contiguous(), &mut []);
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut ring = ArrayRing::<_, 4>::new();
+        let _ = ring.push_back(2);
+        let _ = ring.push_front(0);
+        assert_eq!(ring.insert(1, 1), Ok(()));
+        assert_eq!(ring.insert(3, 3), Ok(()));
+        assert_eq!(ring.insert(0, 4), Err(4));
+        assert_eq!(ring.make_contiguous(), &mut [0, 1, 2, 3]);
+        assert_eq!(ring.remove(1), Some(1));
+        assert_eq!(ring.remove(3), None);
+        assert_eq!(ring.make_contiguous(), &mut [0, 2, 3]);
+    }
+
+    #[test]
+    fn into_array() {
+        let mut ring = ArrayRing::<_, 3>::new();
+        let _ = ring.push_back(2);
+        let _ = ring.push_back(3);
+        let mut ring = ring.into_array().unwrap_err();
+        let _ = ring.push_front(1);
+        assert_eq!(ring.into_array().ok(), Some([1, 2, 3]));
+    }
+
+    #[test]
+    fn borrowed_slots() {
+        let mut slots = [MaybeUninit::uninit(); 2];
+        let mut ring = Ring::from_slots(&mut slots[..]);
+        let _ = ring.push_front(1);
+        let _ = ring.push_front(0);
+        assert_eq!(ring.capacity(), 2);
+        assert_eq!(ring.make_contiguous(), &mut [0, 1]);
+    }
+
+    #[test]
+    fn drops_items() {
+        let drops = Cell::new(0);
+        struct Counted<'a>(&'a Cell<usize>);
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        let mut ring = ArrayRing::<_, 2>::new();
+        let _ = ring.push_back(Counted(&drops));
+        let _ = ring.push_front(Counted(&drops));
+        drop(ring.pop_back());
+        assert_eq!(drops.get(), 1);
+        drop(ring);
+        assert_eq!(drops.get(), 2);
+    }
+}
@@ -0,0 +1,40 @@
+/// An item together with where it starts and ends.
+///
+/// What the positions count depends on where the item came from: [`Lookahead::next_spanned`]
+/// counts consumed items, while a lexer over text would typically count bytes.
+///
+/// [`Lookahead::next_spanned`]: crate::Lookahead::next_spanned
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Spanned<T> {
+    /// The item.
+    pub value: T,
+    /// The position at which the item starts.
+    pub start: usize,
+    /// The position just past the end of the item.
+    pub end: usize,
+}
+
+impl<T> Spanned<T> {
+    /// Create a [`Spanned`] item from `start` up to `end`.
+    pub fn new(value: T, start: usize, end: usize) -> Self {
+        Spanned { value, start, end }
+    }
+
+    /// Return the number of positions the item covers.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Return `true` if the item covers no positions.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Apply `func` to the item, keeping its span.
+    pub fn map<U, F>(self, func: F) -> Spanned<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Spanned::new(func(self.value), self.start, self.end)
+    }
+}
@@ -0,0 +1,248 @@
+use alloc::collections::VecDeque;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// A source of values that become available asynchronously.
+///
+/// This mirrors the `Stream` trait found in the async ecosystem, so that adapting an existing
+/// stream only takes forwarding `poll_next`.
+pub trait Stream {
+    /// The type of the values yielded by the stream.
+    type Item;
+
+    /// Attempt to pull the next value out of the stream.
+    ///
+    /// Returns `Poll::Pending` if no value is available yet, in which case the current task is
+    /// woken once the stream can make progress, and `Poll::Ready(None)` once the stream has
+    /// ended.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+
+    /// Return the bounds on the remaining length of the stream.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
+
+impl<S> Stream for &mut S
+where
+    S: Stream + Unpin + ?Sized,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut **self).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (**self).size_hint()
+    }
+}
+
+/// A stream with arbitrary lookahead.
+///
+/// This is the asynchronous counterpart to [`Lookahead`].
+///
+/// [`Lookahead`]: crate::Lookahead
+#[derive(Clone, Debug)]
+pub struct LookaheadStream<S: Stream> {
+    stream: S,
+    done: bool,
+    queue: VecDeque<S::Item>,
+}
+
+impl<S: Stream> LookaheadStream<S> {
+    /// Create a [`LookaheadStream`] over the given stream.
+    pub fn new(stream: S) -> Self {
+        LookaheadStream {
+            stream,
+            done: false,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Create a [`LookaheadStream`] over the given stream with the specified capacity.
+    pub fn with_capacity(stream: S, capacity: usize) -> Self {
+        LookaheadStream {
+            stream,
+            done: false,
+            queue: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl<S: Stream + Unpin> LookaheadStream<S> {
+    /// Attempt to return a reference to the item `n` items ahead without advancing the stream.
+    ///
+    /// Items are pulled from the underlying stream until the `n`th item is buffered. If the
+    /// stream is not ready, `Poll::Pending` is returned and the items pulled so far stay
+    /// buffered.
+    pub fn poll_lookahead(&mut self, cx: &mut Context<'_>, n: usize) -> Poll<Option<&S::Item>> {
+        match self.poll_fill(cx, n) {
+            Poll::Ready(()) => Poll::Ready(self.queue.get(n)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Return a reference to the item `n` items ahead without advancing the stream.
+    ///
+    /// When `n` is `0`, this is the item that would otherwise have been returned from `.next()`.
+    pub async fn lookahead(&mut self, n: usize) -> Option<&S::Item> {
+        Fill { stream: self, n }.await;
+        self.queue.get(n)
+    }
+
+    /// Return the next item, advancing the stream.
+    #[allow(clippy::should_implement_trait)]
+    pub async fn next(&mut self) -> Option<S::Item> {
+        Fill { stream: self, n: 0 }.await;
+        self.queue.pop_front()
+    }
+
+    fn poll_fill(&mut self, cx: &mut Context<'_>, n: usize) -> Poll<()> {
+        while self.queue.len() <= n && !self.done {
+            match Pin::new(&mut self.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => self.queue.push_back(item),
+                Poll::Ready(None) => self.done = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+// Buffered items are never pinned, so only the underlying stream matters.
+impl<S: Stream + Unpin> Unpin for LookaheadStream<S> {}
+
+impl<S: Stream + Unpin> Stream for LookaheadStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match this.poll_fill(cx, 0) {
+            Poll::Ready(()) => Poll::Ready(this.queue.pop_front()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let queued = self.queue.len();
+        if self.done {
+            return (queued, Some(queued));
+        }
+        let (lower, upper) = self.stream.size_hint();
+        (lower + queued, upper.map(|n| n + queued))
+    }
+}
+
+/// A future that buffers the first `n + 1` items of a [`LookaheadStream`].
+struct Fill<'a, S: Stream> {
+    stream: &'a mut LookaheadStream<S>,
+    n: usize,
+}
+
+impl<'a, S: Stream + Unpin> Future for Fill<'a, S> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        this.stream.poll_fill(cx, this.n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::ptr;
+    use core::task::{RawWaker, RawWakerVTable, Waker};
+
+    /// A stream that is pending on every other poll.
+    struct Flaky {
+        items: vec::IntoIter<i32>,
+        ready: bool,
+    }
+
+    impl Flaky {
+        fn new(items: Vec<i32>) -> Self {
+            Flaky {
+                items: items.into_iter(),
+                ready: false,
+            }
+        }
+    }
+
+    impl Stream for Flaky {
+        type Item = i32;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<i32>> {
+            self.ready = !self.ready;
+            if self.ready {
+                Poll::Ready(self.items.next())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        // SAFETY: the vtable functions ignore the data pointer.
+        unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn poll_lookahead() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut stream = LookaheadStream::new(Flaky::new(vec![1, 2]));
+        assert_eq!(stream.poll_lookahead(&mut cx, 1), Poll::Pending);
+        assert_eq!(stream.poll_lookahead(&mut cx, 1), Poll::Ready(Some(&2)));
+        assert_eq!(stream.size_hint(), (2, None));
+    }
+
+    #[test]
+    fn lookahead() {
+        let mut stream = LookaheadStream::new(Flaky::new(vec![1, 2]));
+        block_on(async {
+            assert_eq!(stream.lookahead(1).await, Some(&2));
+            assert_eq!(stream.lookahead(2).await, None);
+            assert_eq!(stream.size_hint(), (2, Some(2)));
+            assert_eq!(stream.next().await, Some(1));
+            assert_eq!(stream.lookahead(0).await, Some(&2));
+        });
+    }
+
+    #[test]
+    fn poll_next() {
+        let mut inner = LookaheadStream::new(Flaky::new(vec![1, 2, 3]));
+        let mut outer = LookaheadStream::new(&mut inner);
+        block_on(async {
+            assert_eq!(outer.lookahead(0).await, Some(&1));
+            assert_eq!(outer.next().await, Some(1));
+            assert_eq!(outer.next().await, Some(2));
+        });
+        block_on(async {
+            assert_eq!(inner.next().await, Some(3));
+            assert_eq!(inner.next().await, None);
+        });
+    }
+}
@@ -0,0 +1,172 @@
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+
+/// A set of item sequences, stored as a trie so that the longest one at the front of a
+/// [`Lookahead`] can be found in a single pass.
+///
+/// Each sequence is identified by its entry, which is the order in which it was first inserted.
+///
+/// See [`Lookahead::longest_match`].
+///
+/// [`Lookahead`]: crate::Lookahead
+/// [`Lookahead::longest_match`]: crate::Lookahead::longest_match
+#[derive(Clone, Debug)]
+pub struct TrieSet<T> {
+    nodes: Vec<Node<T>>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node<T> {
+    children: Vec<(T, usize)>,
+    entry: Option<usize>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Node {
+            children: Vec::new(),
+            entry: None,
+        }
+    }
+}
+
+impl<T: PartialEq> TrieSet<T> {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        TrieSet {
+            nodes: alloc::vec![Node::new()],
+            len: 0,
+        }
+    }
+
+    /// Return the number of sequences in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` if the set contains no sequences.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add `sequence` to the set, and return its entry.
+    ///
+    /// If the sequence is already in the set, its existing entry is returned.
+    pub fn insert<S>(&mut self, sequence: S) -> usize
+    where
+        S: IntoIterator<Item = T>,
+    {
+        let mut node = 0;
+        for item in sequence {
+            node = match self.child(node, &item) {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(Node::new());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.push((item, child));
+                    child
+                }
+            };
+        }
+        let len = &mut self.len;
+        *self.nodes[node].entry.get_or_insert_with(|| {
+            *len += 1;
+            *len - 1
+        })
+    }
+
+    /// Return the entry of `sequence`, if it is in the set.
+    pub fn get<'a, S>(&self, sequence: S) -> Option<usize>
+    where
+        S: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        let mut node = 0;
+        for item in sequence {
+            node = self.child(node, item)?;
+        }
+        self.nodes[node].entry
+    }
+
+    /// Return the node reached from `node` by `item`.
+    pub(crate) fn child(&self, node: usize, item: &T) -> Option<usize> {
+        self.nodes[node]
+            .children
+            .iter()
+            .find(|(key, _)| key == item)
+            .map(|&(_, child)| child)
+    }
+
+    /// Return the entry of the sequence that ends at `node`, if any.
+    pub(crate) fn entry(&self, node: usize) -> Option<usize> {
+        self.nodes[node].entry
+    }
+}
+
+impl<T: PartialEq> Default for TrieSet<T> {
+    fn default() -> Self {
+        TrieSet::new()
+    }
+}
+
+impl<T, S> FromIterator<S> for TrieSet<T>
+where
+    T: PartialEq,
+    S: IntoIterator<Item = T>,
+{
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut set = TrieSet::new();
+        for sequence in iter {
+            set.insert(sequence);
+        }
+        set
+    }
+}
+
+/// The longest sequence of a [`TrieSet`] found at the front of a [`Lookahead`].
+///
+/// [`Lookahead`]: crate::Lookahead
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrieMatch {
+    entry: usize,
+    len: usize,
+}
+
+impl TrieMatch {
+    pub(crate) fn new(entry: usize, len: usize) -> Self {
+        TrieMatch { entry, len }
+    }
+
+    /// Return the entry of the matching sequence.
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    /// Return the number of items in the matching sequence.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` if the matching sequence is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert() {
+        let mut set = TrieSet::new();
+        assert_eq!(set.insert("ab".chars()), 0);
+        assert_eq!(set.insert("a".chars()), 1);
+        assert_eq!(set.insert("ab".chars()), 0);
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.get(&['a', 'b']), Some(0));
+        assert_eq!(set.get(&['b']), None);
+        assert_eq!(set.get(&[]), None);
+    }
+}
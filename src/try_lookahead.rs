@@ -0,0 +1,158 @@
+use alloc::collections::VecDeque;
+use core::iter::{Fuse, FusedIterator};
+
+/// An iterator over fallible items with arbitrary lookahead.
+///
+/// Unlike a [`Lookahead`] over the same iterator, a [`TryLookahead`] never buffers past an
+/// error: the first error ends the lookahead window, and no further items are pulled from the
+/// underlying iterator after it.
+///
+/// [`Lookahead`]: crate::Lookahead
+#[derive(Clone, Debug)]
+pub struct TryLookahead<I, T, E> {
+    iter: Fuse<I>,
+    queue: VecDeque<T>,
+    error: Option<E>,
+    failed: bool,
+}
+
+impl<I, T, E> TryLookahead<I, T, E>
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    /// Create a [`TryLookahead`] iterator over the given iterable.
+    pub fn new<U>(iterable: U) -> Self
+    where
+        U: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        TryLookahead {
+            iter: iterable.into_iter().fuse(),
+            queue: VecDeque::new(),
+            error: None,
+            failed: false,
+        }
+    }
+
+    /// Create a [`TryLookahead`] iterator over the given iterable with the specified capacity.
+    pub fn with_capacity<U>(iterable: U, capacity: usize) -> Self
+    where
+        U: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        TryLookahead {
+            iter: iterable.into_iter().fuse(),
+            queue: VecDeque::with_capacity(capacity),
+            error: None,
+            failed: false,
+        }
+    }
+
+    /// Return a reference to the item `n` iterations ahead without advancing the iterator.
+    ///
+    /// If an error occurs at or before that position, a reference to the error is returned
+    /// instead. The error is still returned from `.next()` once every item before it has been
+    /// consumed.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use lookahead::TryLookahead;
+    ///
+    /// let mut iter = TryLookahead::new(vec![Ok(1), Err("bad"), Ok(3)]);
+    ///
+    /// assert_eq!(iter.lookahead(0), Ok(Some(&1)));
+    /// assert_eq!(iter.lookahead(2), Err(&"bad"));
+    ///
+    /// assert_eq!(iter.next(), Some(Ok(1)));
+    /// assert_eq!(iter.next(), Some(Err("bad")));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn lookahead(&mut self, n: usize) -> Result<Option<&T>, &E> {
+        while self.queue.len() <= n && !self.failed {
+            match self.iter.next() {
+                Some(Ok(item)) => self.queue.push_back(item),
+                Some(Err(error)) => {
+                    self.error = Some(error);
+                    self.failed = true;
+                }
+                None => break,
+            }
+        }
+        match (self.queue.get(n), &self.error) {
+            (Some(item), _) => Ok(Some(item)),
+            (None, Some(error)) => Err(error),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl<I, T, E> Iterator for TryLookahead<I, T, E>
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.queue.pop_front() {
+            return Some(Ok(item));
+        }
+        if self.failed {
+            return self.error.take().map(Err);
+        }
+        let item = self.iter.next();
+        if let Some(Err(_)) = item {
+            self.failed = true;
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let queued = self.queue.len() + self.error.is_some() as usize;
+        if self.failed {
+            return (queued, Some(queued));
+        }
+        let (lower, upper) = self.iter.size_hint();
+        (lower + queued, upper.map(|n| n + queued))
+    }
+}
+
+impl<I, T, E> FusedIterator for TryLookahead<I, T, E> where I: Iterator<Item = Result<T, E>> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn lookahead() {
+        let mut iter = TryLookahead::new(vec![Ok::<_, ()>(1), Ok(2)]);
+        assert_eq!(iter.lookahead(1), Ok(Some(&2)));
+        assert_eq!(iter.lookahead(2), Ok(None));
+        assert_eq!(iter.next(), Some(Ok(1)));
+    }
+
+    #[test]
+    fn stops_at_error() {
+        let mut calls = 0;
+        let inner = vec![Ok(1), Err(()), Ok(3)]
+            .into_iter()
+            .inspect(|_| calls += 1);
+        let mut iter = TryLookahead::new(inner);
+        assert_eq!(iter.lookahead(5), Err(&()));
+        assert_eq!(iter.lookahead(0), Ok(Some(&1)));
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(iter.by_ref().collect::<Vec<_>>(), vec![Ok(1), Err(())]);
+        assert_eq!(iter.lookahead(0), Ok(None));
+        drop(iter);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn error_without_lookahead() {
+        let mut iter = TryLookahead::new(vec![Err(1), Ok(2)]);
+        assert_eq!(iter.next(), Some(Err(1)));
+        assert_eq!(iter.next(), None);
+    }
+}